@@ -1,5 +1,6 @@
 use std::{fs::File, io::Read};
 
+use lichess_api::model::{Speed, VariantKey};
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, Result};
@@ -32,12 +33,71 @@ pub struct Config {
     pub lichess: Lichess,
     pub twitch: Twitch,
     pub livestream: Livestream,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub simul: Simul,
+    #[serde(default)]
+    pub teams: Teams,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub opponents: Opponents,
+    #[serde(default)]
+    pub lobby: Lobby,
+    #[serde(default)]
+    pub inbound: Inbound,
+    #[serde(default)]
+    pub viewers: Viewers,
+    #[serde(default)]
+    pub time_controls: TimeControls,
+    #[serde(default)]
+    pub positions: Positions,
+    #[serde(default)]
+    pub correspondence: Correspondence,
+    #[serde(default)]
+    pub rematch: Rematch,
+    #[serde(default)]
+    pub game_chat: GameChat,
+    #[serde(default)]
+    pub spectators: Spectators,
+    #[serde(default)]
+    pub seeks: Seeks,
+    #[serde(default)]
+    pub abort: Abort,
+    #[serde(default)]
+    pub focus: Focus,
+    #[serde(default)]
+    pub tv: Tv,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Lichess {
     pub account: String,
     pub access_token: String,
+    #[serde(default)]
+    pub mode: AccountMode,
+}
+
+/// Which Lichess API the account plays through.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountMode {
+    /// A BOT account, which can only play through challenges.
+    #[default]
+    Bot,
+    /// A regular account through the Board API, which can also seek human opponents.
+    Board,
+}
+
+impl AccountMode {
+    /// Where the game endpoints live, e.g. `/api/board/game/{id}/move/{move}`.
+    pub fn api_path(&self) -> &'static str {
+        match self {
+            Self::Bot => "bot",
+            Self::Board => "board",
+        }
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -54,3 +114,345 @@ pub struct Livestream {
 pub struct Video {
     pub fifo: String,
 }
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Storage {
+    pub directory: String,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self { directory: "archive".to_string() }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Simul {
+    pub enabled: bool,
+    pub boards: usize,
+}
+
+impl Default for Simul {
+    fn default() -> Self {
+        Self { enabled: false, boards: 4 }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Teams {
+    pub enabled: bool,
+    pub lichess_games_between: u32,
+}
+
+impl Default for Teams {
+    fn default() -> Self {
+        Self { enabled: false, lichess_games_between: 3 }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Difficulty {
+    pub initial_rating: u32,
+    pub band: u32,
+    pub band_step: u32,
+    pub max_band: u32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self { initial_rating: 1500, band: 150, band_step: 100, max_band: 800 }
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Opponents {
+    pub policy: OpponentPolicy,
+    /// Bot ids to take turns with under the round robin policy.
+    pub roster: Vec<String>,
+    /// If not empty, only these bots are ever challenged.
+    pub allowlist: Vec<String>,
+    pub blocklist: Vec<String>,
+    /// Never challenge the same bot twice within this many games.
+    pub avoid_repeats: usize,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Lobby {
+    pub enabled: bool,
+    /// How many bots chat picks between - three to five.
+    pub candidates: usize,
+    pub vote_seconds: u64,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Self { enabled: false, candidates: 4, vote_seconds: 20 }
+    }
+}
+
+/// Which challenges from other players are accepted.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Inbound {
+    /// Off by default, leaving challenges from other players unanswered as before.
+    pub enabled: bool,
+    pub variants: Vec<VariantKey>,
+    pub speeds: Vec<Speed>,
+    pub rated: bool,
+    pub casual: bool,
+    pub min_rating: u32,
+    pub max_rating: u32,
+    pub bots: bool,
+    pub humans: bool,
+    /// Challenges beyond this are declined with "later".
+    pub max_queue: usize,
+}
+
+impl Default for Inbound {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            variants: vec![VariantKey::Standard],
+            speeds: vec![
+                Speed::Bullet,
+                Speed::Blitz,
+                Speed::Rapid,
+                Speed::Classical,
+                Speed::Correspondence,
+            ],
+            rated: true,
+            casual: true,
+            min_rating: 0,
+            max_rating: 4000,
+            bots: true,
+            humans: true,
+            max_queue: 3,
+        }
+    }
+}
+
+/// Viewers queueing up to play chat with `!challengeme`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Viewers {
+    pub enabled: bool,
+    pub max_queue: usize,
+}
+
+impl Default for Viewers {
+    fn default() -> Self {
+        Self { enabled: true, max_queue: 10 }
+    }
+}
+
+/// The time controls chat can play at. Each is offered once chat votes its speed on.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TimeControls {
+    pub catalogue: Vec<TimeControl>,
+}
+
+impl Default for TimeControls {
+    fn default() -> Self {
+        Self {
+            catalogue: vec![
+                TimeControl::clock(Speed::Bullet, 120, 1),
+                TimeControl { always_on: true, ..TimeControl::clock(Speed::Blitz, 300, 3) },
+                TimeControl::clock(Speed::Rapid, 600, 10),
+                TimeControl::clock(Speed::Classical, 1800, 0),
+                TimeControl::days(3),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TimeControl {
+    pub speed: Speed,
+    /// Clock limit in seconds.
+    #[serde(default)]
+    pub limit: u32,
+    /// Clock increment in seconds.
+    #[serde(default)]
+    pub increment: u32,
+    /// Days per move, for correspondence games without a clock.
+    #[serde(default)]
+    pub days: Option<u32>,
+    /// Offered whatever chat votes.
+    #[serde(default)]
+    pub always_on: bool,
+}
+
+impl TimeControl {
+    pub fn clock(speed: Speed, limit: u32, increment: u32) -> Self {
+        Self { speed, limit, increment, days: None, always_on: false }
+    }
+
+    pub fn days(days: u32) -> Self {
+        Self {
+            speed: Speed::Correspondence,
+            limit: 0,
+            increment: 0,
+            days: days.into(),
+            always_on: false,
+        }
+    }
+
+    /// As shown on stream, e.g. "5+3" or "3 days".
+    pub fn label(&self) -> String {
+        match self.days {
+            Some(1) => "1 day".to_string(),
+            Some(days) => format!("{} days", days),
+            None if self.limit % 60 == 0 => format!("{}+{}", self.limit / 60, self.increment),
+            None => format!("{}s+{}", self.limit, self.increment),
+        }
+    }
+}
+
+/// The library of starting positions chat can vote for.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Positions {
+    pub path: String,
+}
+
+impl Default for Positions {
+    fn default() -> Self {
+        Self { path: "assets/positions.json".to_string() }
+    }
+}
+
+/// How long chat gets to vote on each move in a correspondence game. These games are only
+/// played once chat votes correspondence on, which offers the catalogue's days per move.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Correspondence {
+    pub turn_hours: u64,
+    /// A move with this many votes is played without waiting for the deadline.
+    pub vote_quota: u32,
+}
+
+impl Default for Correspondence {
+    fn default() -> Self {
+        Self { turn_hours: 12, vote_quota: 10 }
+    }
+}
+
+/// After a game against an opponent we challenged, chat votes whether to play them again.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Rematch {
+    pub enabled: bool,
+    pub vote_seconds: u64,
+}
+
+impl Default for Rematch {
+    fn default() -> Self {
+        Self { enabled: true, vote_seconds: 20 }
+    }
+}
+
+/// Games are aborted if the opponent doesn't make their first move in time.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Abort {
+    pub first_move_seconds: u64,
+}
+
+impl Default for Abort {
+    fn default() -> Self {
+        Self { first_move_seconds: 30 }
+    }
+}
+
+/// Which game is on stream when several are being played.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Focus {
+    /// A game stays on stream at least this long before a more urgent one takes over.
+    pub min_dwell_seconds: u64,
+}
+
+impl Default for Focus {
+    fn default() -> Self {
+        Self { min_dwell_seconds: 15 }
+    }
+}
+
+/// Lichess TV's featured game, shown while there's no game of our own.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Tv {
+    pub enabled: bool,
+    /// A recorded feed to replay instead of the live one, one message per line, e.g.
+    /// "assets/tv_feed.ndjson".
+    pub recording: Option<String>,
+    /// How long each move of a recording is shown.
+    pub replay_millis: u64,
+}
+
+impl Default for Tv {
+    fn default() -> Self {
+        Self { enabled: true, recording: None, replay_millis: 1000 }
+    }
+}
+
+/// The Lichess game chat, shown on stream and posted to by the bot.
+/// "{channel}" in a message is replaced with the Twitch channel.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct GameChat {
+    pub enabled: bool,
+    /// Posted when a game starts.
+    pub greeting: Option<String>,
+    /// Posted when a game ends.
+    pub farewell: Option<String>,
+    /// Posts what chat voted for after each of our moves.
+    pub post_votes: bool,
+}
+
+impl Default for GameChat {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            greeting: "Hi! You're playing Twitch chat: https://twitch.tv/{channel}"
+                .to_string()
+                .into(),
+            farewell: "Good game!".to_string().into(),
+            post_votes: false,
+        }
+    }
+}
+
+/// Spectators in the Lichess game chat voting with the same commands as Twitch chat.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Spectators {
+    pub enabled: bool,
+    /// How much a spectator's vote counts compared to one from Twitch.
+    pub weight: f32,
+}
+
+impl Default for Spectators {
+    fn default() -> Self {
+        Self { enabled: true, weight: 0.5 }
+    }
+}
+
+/// Seeking human opponents in the Lichess lobby, which needs the Board API account mode.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Seeks {
+    pub enabled: bool,
+    /// Opponents are sought within this many points of our rating.
+    pub rating_range: u32,
+    /// A seek nobody accepts is withdrawn after this long, and another game found.
+    pub seek_seconds: u64,
+}
+
+impl Default for Seeks {
+    fn default() -> Self {
+        Self { enabled: true, rating_range: 300, seek_seconds: 120 }
+    }
+}
+
+/// How the next bot to challenge is chosen from those online.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpponentPolicy {
+    /// Any bot, favouring lower rated ones.
+    WeightedRandom,
+    /// Bots around chat's estimated strength.
+    #[default]
+    RatingMatched,
+    /// Each bot in the roster in turn, skipping any that are offline.
+    RoundRobin,
+}
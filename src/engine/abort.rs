@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::config;
+use crate::engine::events::internal::{EventSender, GameNotification, Notification};
+use crate::lichess::game::{Game, GameId};
+
+/// Aborts games whose opponent doesn't make their first move in time. Chat is never timed -
+/// the game is only ever aborted while it's the opponent's first move, never once both sides
+/// have moved.
+pub struct AbortPolicy {
+    first_move_duration: Duration,
+    /// Games waiting on the opponent's first move.
+    timers: HashMap<GameId, JoinHandle<()>>,
+    event_sender: EventSender,
+}
+
+impl AbortPolicy {
+    pub fn new(config: &config::Abort, event_sender: EventSender) -> Self {
+        Self {
+            first_move_duration: Duration::from_secs(config.first_move_seconds),
+            timers: Default::default(),
+            event_sender,
+        }
+    }
+
+    /// Starts timing the opponent if the game is waiting on their first move.
+    pub fn watch(&mut self, game: &Game) {
+        if !game.is_correspondence() && is_awaiting_first_move(game) {
+            self.start_timer(&game.game_id);
+        }
+    }
+
+    /// The opponent's clock starts after our first move and stops after theirs.
+    pub fn record_move(&mut self, game: &Game, was_us: bool) {
+        if was_us {
+            self.watch(game);
+        } else {
+            self.remove_game(&game.game_id);
+        }
+    }
+
+    /// Checked again when the opponent's time is up, as a game picked up after a restart
+    /// only knows its moves once the full game arrives.
+    pub fn should_abort(&self, game: &Game) -> bool {
+        is_awaiting_first_move(game)
+    }
+
+    pub fn remove_game(&mut self, game_id: &str) {
+        if let Some(timer_handle) = self.timers.remove(game_id) {
+            timer_handle.abort();
+        }
+    }
+
+    fn start_timer(&mut self, game_id: &str) {
+        if self.timers.contains_key(game_id) {
+            return;
+        }
+
+        let mut event_sender = self.event_sender.clone();
+        let first_move_duration = self.first_move_duration;
+        let abortable_id = game_id.to_string();
+
+        let timer_handle = tokio::task::spawn(async move {
+            tokio::time::sleep(first_move_duration).await;
+            let notification = GameNotification::GameAbortable { game_id: abortable_id };
+            event_sender.send_notification(Notification::Game(notification));
+        });
+
+        self.timers.insert(game_id.to_string(), timer_handle);
+    }
+}
+
+/// It's the opponent's turn and they haven't moved yet.
+fn is_awaiting_first_move(game: &Game) -> bool {
+    let they_move_first = game.start_position.board.side_to_move() != game.us.color;
+    let they_moved = game.move_count() >= if they_move_first { 1 } else { 2 };
+
+    !they_moved && !game.is_our_turn && !game.finished
+}
@@ -0,0 +1,381 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+use chess::ChessMove;
+use lichess_api::model::{Speed, VariantKey};
+
+use crate::error::Result;
+use crate::lichess::game::Game;
+use crate::lichess::position::Position;
+
+use super::votes::audit::{CloseReason, GameAudit};
+
+/// Appends every finished game to a PGN file, with each of chat's moves marked by whether
+/// chat voted for it or a random move was played for lack of votes.
+pub struct PgnArchive {
+    path: PathBuf,
+    our_name: String,
+    channel: String,
+}
+
+impl PgnArchive {
+    pub fn new(path: PathBuf, our_name: &str, channel: &str) -> Self {
+        Self { path, our_name: our_name.to_string(), channel: channel.to_string() }
+    }
+
+    pub fn write(&self, game: &Game, audit: Option<&GameAudit>) -> Result<()> {
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        write!(file, "{}\n\n", self.pgn(game, audit))?;
+
+        Ok(())
+    }
+
+    fn pgn(&self, game: &Game, audit: Option<&GameAudit>) -> String {
+        let mut pgn: String = self
+            .headers(game)
+            .iter()
+            .map(|(tag, value)| format!("[{} \"{}\"]\n", tag, value))
+            .collect();
+        pgn.push('\n');
+        pgn.push_str(&movetext(game, audit));
+        pgn.push(' ');
+        pgn.push_str(result(game));
+        pgn
+    }
+
+    fn headers(&self, game: &Game) -> Vec<(&'static str, String)> {
+        let our_name = self.our_name.to_string();
+        let (white, black) = match game.us.color {
+            chess::Color::White => {
+                ((our_name, &game.us), (game.opponent.name.to_string(), &game.opponent))
+            }
+            chess::Color::Black => {
+                ((game.opponent.name.to_string(), &game.opponent), (our_name, &game.us))
+            }
+        };
+
+        let mut headers = vec![
+            ("Event", event(game)),
+            ("Site", format!("https://lichess.org/{}", game.game_id)),
+            ("Date", date(game)),
+            ("White", white.0),
+            ("Black", black.0),
+            ("Result", result(game).to_string()),
+        ];
+        if let Some(rating) = white.1.rating {
+            headers.push(("WhiteElo", rating.to_string()));
+        }
+        if let Some(rating) = black.1.rating {
+            headers.push(("BlackElo", rating.to_string()));
+        }
+
+        let time_control = match &game.clock_settings {
+            Some(clock) if !game.is_correspondence() => {
+                format!("{}+{}", clock.initial_seconds, clock.increment)
+            }
+            _ => "-".to_string(),
+        };
+        headers.push(("TimeControl", time_control));
+        headers.push(("Termination", termination(game).to_string()));
+
+        match game.variant {
+            VariantKey::Chess960 => headers.push(("Variant", "Chess960".to_string())),
+            VariantKey::FromPosition => headers.push(("Variant", "From Position".to_string())),
+            _ => {}
+        }
+        if game.start_position.board != Position::default().board {
+            headers.push(("SetUp", "1".to_string()));
+            let fen = game.initial_fen.clone();
+            headers.push(("FEN", fen.unwrap_or_else(|| game.start_position.board.to_string())));
+        }
+
+        headers.push(("TwitchChannel", self.channel.to_string()));
+        headers
+    }
+}
+
+/// The moves in SAN, with the mover's clock after each and whether chat's moves were voted.
+fn movetext(game: &Game, audit: Option<&GameAudit>) -> String {
+    let sources = move_sources(audit);
+    let mut next_source = 0;
+
+    let mut position = game.start_position.clone();
+    let first_mover = position.board.side_to_move();
+    let moves = game.move_history.iter().filter(|m| !m.is_empty());
+
+    let mut tokens = Vec::<String>::new();
+    for (ply, uci) in moves.enumerate() {
+        let Some(chess_move) = ChessMove::from_str(uci).ok() else {
+            break;
+        };
+        let Some(san) = position.san(chess_move) else {
+            break;
+        };
+
+        let mover = position.board.side_to_move();
+        let move_number = (ply + if first_mover == chess::Color::Black { 1 } else { 0 }) / 2 + 1;
+        if mover == chess::Color::White {
+            tokens.push(format!("{}.", move_number));
+        } else if ply == 0 {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(san);
+
+        let mut comment = Vec::<String>::new();
+        if let Some(Some(clock)) = game.move_clocks.get(ply) {
+            comment.push(format!("[%clk {}]", clock_time(*clock)));
+        }
+        if mover == game.us.color {
+            // Skips past decisions that weren't moves, e.g. offering a draw.
+            let found = sources[next_source..].iter().position(|(played, _)| played == uci);
+            if let Some(offset) = found {
+                comment.push(sources[next_source + offset].1.to_string());
+                next_source += offset + 1;
+            }
+        }
+        if !comment.is_empty() {
+            tokens.push(format!("{{ {} }}", comment.join(" ")));
+        }
+
+        let Some(next) = position.make_move(chess_move) else {
+            break;
+        };
+        position = next;
+    }
+
+    tokens.join(" ")
+}
+
+/// What chat played, in order, and whether it came from their votes or a random fallback.
+fn move_sources(audit: Option<&GameAudit>) -> Vec<(String, &'static str)> {
+    let Some(audit) = audit else {
+        return Default::default();
+    };
+
+    audit
+        .decisions
+        .iter()
+        .filter_map(|decision| {
+            let source = match decision.reason {
+                CloseReason::TimerExpired => "chat",
+                CloseReason::NoVotes => "fallback",
+                CloseReason::Delayed | CloseReason::Discarded => return None,
+            };
+            (decision.played.clone()?, source).into()
+        })
+        .collect()
+}
+
+fn event(game: &Game) -> String {
+    let speed = match game.speed {
+        Speed::UltraBullet => "UltraBullet",
+        Speed::Bullet => "Bullet",
+        Speed::Blitz => "Blitz",
+        Speed::Rapid => "Rapid",
+        Speed::Classical => "Classical",
+        Speed::Correspondence => "Correspondence",
+    };
+    let rated = if game.rated { "Rated" } else { "Casual" };
+
+    format!("{} {} game", rated, speed)
+}
+
+fn result(game: &Game) -> &'static str {
+    match (game.winner, game.status.as_str()) {
+        (Some(chess::Color::White), _) => "1-0",
+        (Some(chess::Color::Black), _) => "0-1",
+        (None, "started" | "created" | "aborted" | "noStart") => "*",
+        (None, _) => "1/2-1/2",
+    }
+}
+
+/// Named as on Lichess' own PGN exports.
+fn termination(game: &Game) -> &'static str {
+    match game.status.as_str() {
+        "outoftime" => "Time forfeit",
+        "timeout" => "Abandoned",
+        "started" | "created" | "aborted" | "noStart" => "Unterminated",
+        _ => "Normal",
+    }
+}
+
+/// As h:mm:ss, which is what `%clk` expects.
+fn clock_time(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    format!("{}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60)
+}
+
+/// The day the game started as yyyy.mm.dd, in UTC.
+fn date(game: &Game) -> String {
+    let Ok(since_epoch) = game.started_at.duration_since(UNIX_EPOCH) else {
+        return "????.??.??".to_string();
+    };
+
+    // Days since the epoch to a civil date, from Howard Hinnant's chrono-compatible algorithms.
+    let z = (since_epoch.as_secs() / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, SystemTime};
+
+    use crate::stream::model::{ClockSettings, Player, Timer};
+
+    use super::*;
+
+    fn game() -> Game {
+        let player = |name: &str, color| Player {
+            name: name.to_string(),
+            color,
+            rating: None,
+            timer: Timer::new(0),
+        };
+
+        Game {
+            game_id: "abcdefgh".to_string(),
+            speed: Speed::Blitz,
+            variant: VariantKey::Standard,
+            rated: true,
+            timestamp: Instant::now(),
+            clock_settings: ClockSettings { initial_seconds: 180, increment: 2 }.into(),
+            position: Position::default(),
+            start_position: Position::default(),
+            initial_fen: None,
+            move_history: Default::default(),
+            move_clocks: Default::default(),
+            last_move: None,
+            is_our_turn: true,
+            us: player("Twitch", chess::Color::White),
+            opponent: player("opponent", chess::Color::Black),
+            timers_started: false,
+            finished: true,
+            started_at: SystemTime::now(),
+            status: "mate".to_string(),
+            winner: chess::Color::White.into(),
+        }
+    }
+
+    fn header(game: &Game, tag: &str) -> Option<String> {
+        let archive = PgnArchive::new(PathBuf::new(), "TTVPlaysChess", "ttvplayschess");
+        let headers = archive.headers(game);
+        headers.into_iter().find(|(name, _)| *name == tag).map(|(_, value)| value)
+    }
+
+    #[test]
+    fn time_control_is_in_seconds() {
+        let mut game = game();
+        assert_eq!(header(&game, "TimeControl").unwrap(), "180+2");
+
+        game.clock_settings = ClockSettings { initial_seconds: 15, increment: 0 }.into();
+        assert_eq!(header(&game, "TimeControl").unwrap(), "15+0");
+
+        game.clock_settings = ClockSettings { initial_seconds: 90, increment: 1 }.into();
+        assert_eq!(header(&game, "TimeControl").unwrap(), "90+1");
+
+        game.speed = Speed::Correspondence;
+        assert_eq!(header(&game, "TimeControl").unwrap(), "-");
+    }
+
+    #[test]
+    fn event_says_whether_the_game_was_rated() {
+        let mut game = game();
+        assert_eq!(header(&game, "Event").unwrap(), "Rated Blitz game");
+
+        game.rated = false;
+        assert_eq!(header(&game, "Event").unwrap(), "Casual Blitz game");
+    }
+
+    #[test]
+    fn fen_header_keeps_lichess_starting_fen() {
+        let mut game = game();
+        assert_eq!(header(&game, "FEN"), None);
+
+        let fen = "nrbkqbrn/pppppppp/8/8/8/8/PPPPPPPP/NRBKQBRN w GBgb - 0 1";
+        game.variant = VariantKey::Chess960;
+        game.start_position = Position::from_fen(fen, true).unwrap();
+        game.initial_fen = fen.to_string().into();
+        assert_eq!(header(&game, "SetUp").unwrap(), "1");
+        assert_eq!(header(&game, "FEN").unwrap(), fen);
+    }
+
+    #[test]
+    fn results_and_terminations() {
+        let mut game = game();
+        assert_eq!(result(&game), "1-0");
+        assert_eq!(termination(&game), "Normal");
+
+        game.winner = None;
+        game.status = "stalemate".to_string();
+        assert_eq!(result(&game), "1/2-1/2");
+
+        game.status = "aborted".to_string();
+        assert_eq!(result(&game), "*");
+        assert_eq!(termination(&game), "Unterminated");
+
+        game.winner = chess::Color::Black.into();
+        game.status = "outoftime".to_string();
+        assert_eq!(result(&game), "0-1");
+        assert_eq!(termination(&game), "Time forfeit");
+    }
+
+    #[test]
+    fn clocks_are_hours_minutes_and_seconds() {
+        assert_eq!(clock_time(0), "0:00:00");
+        assert_eq!(clock_time(61_999), "0:01:01");
+        assert_eq!(clock_time(3_725_000), "1:02:05");
+    }
+
+    #[test]
+    fn dates_are_in_utc() {
+        let mut game = game();
+        let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+
+        game.started_at = at(0);
+        assert_eq!(date(&game), "1970.01.01");
+
+        game.started_at = at(951_782_400);
+        assert_eq!(date(&game), "2000.02.29");
+
+        game.started_at = at(1_709_251_199);
+        assert_eq!(date(&game), "2024.02.29");
+
+        game.started_at = at(1_709_251_200);
+        assert_eq!(date(&game), "2024.03.01");
+    }
+
+    #[test]
+    fn movetext_has_move_numbers_and_clocks() {
+        let mut game = game();
+        game.move_history = vec!["e2e4".to_string(), "e7e5".to_string(), "g1f3".to_string()];
+        game.move_clocks = vec![Some(179_000), Some(178_500), None];
+
+        assert_eq!(movetext(&game, None), "1. e4 { [%clk 0:02:59] } e5 { [%clk 0:02:58] } 2. Nf3");
+    }
+
+    #[test]
+    fn movetext_from_a_position_with_black_to_move() {
+        let mut game = game();
+        game.start_position = Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1", false).unwrap();
+        game.move_history = vec!["e8d8".to_string(), "e2e4".to_string()];
+
+        assert_eq!(movetext(&game, None), "1... Kd8 2. e4");
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chess::ChessMove;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::engine::events::internal::{EventSender, Notification};
+use crate::engine::votes::Username;
+use crate::error::Result;
+use crate::lichess::game::GameId;
+use crate::stream::model::CorrespondencePanel;
+
+/// How often deadlines are checked.
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+/// Chat's move is always played at least this long before Lichess would flag us.
+const SAFETY_MARGIN: Duration = Duration::from_secs(60 * 60);
+
+/// Daily games, voted on by chat over hours and across streams. Votes are persisted so
+/// that a restart doesn't lose them.
+pub struct Correspondence {
+    path: PathBuf,
+    games: Vec<CorrespondenceGame>,
+    turn_duration: Duration,
+    quota: u32,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct CorrespondenceGame {
+    game_id: GameId,
+    opponent: String,
+    /// Moves in UCI, since that's what chat votes with.
+    votes: HashMap<Username, String>,
+    /// Unix seconds when chat's move is played, or None on the opponent's turn.
+    deadline: Option<u64>,
+}
+
+impl Correspondence {
+    /// Loads open games from the path, starting afresh if there aren't any yet.
+    pub fn load(config: &config::Correspondence, path: PathBuf, event_sender: EventSender) -> Self {
+        let games = File::open(&path)
+            .map_err(crate::error::Error::from)
+            .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?));
+
+        let games = match games {
+            Ok(games) => games,
+            Err(error) => {
+                log::info!("[Correspondence] No games loaded from {:?}: {}", &path, error);
+                Default::default()
+            }
+        };
+
+        let mut event_sender = event_sender;
+        tokio::task::spawn(async move {
+            // Games get a chance to arrive from Lichess before the first tick.
+            let start = tokio::time::Instant::now() + TICK_INTERVAL;
+            let mut interval = tokio::time::interval_at(start, TICK_INTERVAL);
+            loop {
+                interval.tick().await;
+                event_sender.send_notification(Notification::CorrespondenceTick);
+            }
+        });
+
+        Self {
+            path,
+            games,
+            turn_duration: Duration::from_secs(config.turn_hours * 60 * 60),
+            quota: config.vote_quota.max(1),
+        }
+    }
+
+    pub fn contains(&self, game_id: &str) -> bool {
+        self.games.iter().any(|game| game.game_id == game_id)
+    }
+
+    /// Games are numbered from 1 as shown on stream, e.g. `!d1 e2e4`.
+    pub fn game_id(&self, slot: usize) -> Option<&GameId> {
+        self.games.get(slot.checked_sub(1)?).map(|game| &game.game_id)
+    }
+
+    pub fn add_game(&mut self, game_id: &str, opponent: &str) {
+        if self.contains(game_id) {
+            return;
+        }
+
+        log::info!("[Correspondence] Tracking game {} against {}", game_id, opponent);
+        self.games.push(CorrespondenceGame {
+            game_id: game_id.to_string(),
+            opponent: opponent.to_string(),
+            votes: Default::default(),
+            deadline: None,
+        });
+        self.save();
+    }
+
+    pub fn remove_game(&mut self, game_id: &str) {
+        let count = self.games.len();
+        self.games.retain(|game| game.game_id != game_id);
+        if self.games.len() != count {
+            self.save();
+        }
+    }
+
+    /// Drops games that are no longer open, e.g. ones that finished while we were offline.
+    pub fn retain(&mut self, is_open: impl Fn(&str) -> bool) {
+        let count = self.games.len();
+        self.games.retain(|game| is_open(&game.game_id));
+        if self.games.len() != count {
+            self.save();
+        }
+    }
+
+    /// Starts the clock on chat's move, unless it's already running from a previous stream.
+    /// A zero time left means Lichess didn't say.
+    pub fn start_turn(&mut self, game_id: &str, time_left: Duration) {
+        let turn_duration = match time_left.is_zero() {
+            true => self.turn_duration,
+            false => self.turn_duration.min(time_left.saturating_sub(SAFETY_MARGIN)),
+        };
+        let Some(game) = self.game_mut(game_id) else {
+            return;
+        };
+
+        if game.deadline.is_some() {
+            return;
+        }
+
+        game.deadline = (unix_now() + turn_duration.as_secs()).into();
+        self.save();
+    }
+
+    pub fn end_turn(&mut self, game_id: &str) {
+        let Some(game) = self.game_mut(game_id) else {
+            return;
+        };
+
+        if game.deadline.is_none() && game.votes.is_empty() {
+            return;
+        }
+
+        game.deadline = None;
+        game.votes.clear();
+        self.save();
+    }
+
+    /// Returns true once the move has enough votes to be played straight away.
+    pub fn add_vote(&mut self, game_id: &str, user: Username, chess_move: ChessMove) -> bool {
+        let quota = self.quota;
+        let Some(game) = self.game_mut(game_id) else {
+            return false;
+        };
+
+        if game.deadline.is_none() {
+            log::info!("[Correspondence] Ignoring vote from {} - not our turn", &user);
+            return false;
+        }
+
+        game.votes.insert(user, chess_move.to_string());
+        let reached_quota = game.top_vote().map(|(_, votes)| votes >= quota).unwrap_or(false);
+        self.save();
+
+        reached_quota
+    }
+
+    pub fn top_vote(&self, game_id: &str) -> Option<ChessMove> {
+        let game = self.games.iter().find(|game| game.game_id == game_id)?;
+        game.top_vote().map(|(chess_move, _)| chess_move)
+    }
+
+    /// Games whose deadline has passed.
+    pub fn due_games(&self) -> Vec<GameId> {
+        let now = unix_now();
+        self.games
+            .iter()
+            .filter(|game| game.deadline.map(|deadline| deadline <= now).unwrap_or(false))
+            .map(|game| game.game_id.to_string())
+            .collect()
+    }
+
+    pub fn panels(&self) -> Vec<CorrespondencePanel> {
+        self.games
+            .iter()
+            .enumerate()
+            .map(|(index, game)| {
+                let top_vote =
+                    game.top_vote().map(|(chess_move, votes)| (chess_move.to_string(), votes));
+                CorrespondencePanel {
+                    slot: index + 1,
+                    opponent: game.opponent.to_string(),
+                    top_vote,
+                    quota: self.quota,
+                    deadline: game.deadline,
+                }
+            })
+            .collect()
+    }
+
+    fn game_mut(&mut self, game_id: &str) -> Option<&mut CorrespondenceGame> {
+        self.games.iter_mut().find(|game| game.game_id == game_id)
+    }
+
+    fn save(&self) {
+        if let Err(error) = self.write() {
+            log::error!("[Correspondence] Failed to save games to {:?}: {}", &self.path, error);
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        let writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(writer, &self.games)?;
+
+        Ok(())
+    }
+}
+
+impl CorrespondenceGame {
+    fn top_vote(&self) -> Option<(ChessMove, u32)> {
+        let mut tally = HashMap::<&String, u32>::default();
+        for chess_move in self.votes.values() {
+            *tally.entry(chess_move).or_default() += 1;
+        }
+
+        // Ties go to the first alphabetically, so the top vote doesn't flicker between them.
+        let (chess_move, votes) =
+            tally.into_iter().max_by(|(l_move, l_votes), (r_move, r_votes)| {
+                l_votes.cmp(r_votes).then_with(|| r_move.cmp(l_move))
+            })?;
+
+        (ChessMove::from_str(chess_move).ok()?, votes).into()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
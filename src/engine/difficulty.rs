@@ -0,0 +1,146 @@
+use std::collections::{HashMap, VecDeque};
+
+use lichess_api::model::{Speed, VariantKey};
+
+use crate::config;
+use crate::lichess::game::GameOutcome;
+use crate::twitch::command::GameMode;
+
+/// How many recent results count towards chat's win rate.
+const RECENT_RESULTS: usize = 20;
+/// How far a single result moves the estimate, as with Elo's K-factor.
+const K_FACTOR: f32 = 32.0;
+/// The estimate never drops below this, however badly chat plays.
+const MIN_ESTIMATE: f32 = 400.0;
+
+/// Which of chat's strengths a game says something about. Like Lichess' own ratings there's
+/// one per speed, and Chess960 has its own whatever the speed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RatingPool {
+    Speed(GameMode),
+    Chess960,
+}
+
+/// Keeps a running estimate of chat's strength in each pool and targets opponents around it.
+pub struct DifficultyController {
+    initial_rating: f32,
+    estimates: HashMap<RatingPool, Estimate>,
+    band: u32,
+    band_step: u32,
+    max_band: u32,
+    widening: u32,
+}
+
+#[derive(Default)]
+struct Estimate {
+    rating: f32,
+    results: VecDeque<GameOutcome>,
+}
+
+impl RatingPool {
+    pub fn new(speed: &Speed, variant: &VariantKey) -> Self {
+        match variant {
+            VariantKey::Chess960 => Self::Chess960,
+            _ => Self::Speed(GameMode::from_speed(speed)),
+        }
+    }
+}
+
+impl DifficultyController {
+    pub fn new(config: &config::Difficulty) -> Self {
+        Self {
+            initial_rating: config.initial_rating as f32,
+            estimates: Default::default(),
+            band: config.band,
+            band_step: config.band_step,
+            max_band: config.max_band.max(config.band),
+            widening: 0,
+        }
+    }
+
+    /// Pools chat hasn't played in yet start from the configured rating.
+    fn rating(&self, pool: RatingPool) -> f32 {
+        self.estimates.get(&pool).map_or(self.initial_rating, |estimate| estimate.rating)
+    }
+
+    pub fn estimate(&self, pool: RatingPool) -> u32 {
+        self.rating(pool).round() as u32
+    }
+
+    pub fn band(&self) -> u32 {
+        (self.band + self.widening).min(self.max_band)
+    }
+
+    /// The rating opponents are aimed at. If chat keeps winning (or losing) against
+    /// opponents at its estimate, the target is nudged up (or down) to even things out.
+    pub fn target(&self, pool: RatingPool) -> u32 {
+        let offset = match self.win_rate(pool) {
+            Some(win_rate) => (win_rate - 0.5) * self.band as f32,
+            None => 0.0,
+        };
+
+        (self.rating(pool) + offset).max(MIN_ESTIMATE).round() as u32
+    }
+
+    pub fn target_range(&self, pool: RatingPool) -> (u32, u32) {
+        let (target, band) = (self.target(pool), self.band());
+        (target.saturating_sub(band), target + band)
+    }
+
+    /// Weighting for picking an opponent - zero outside the band, highest at the target.
+    pub fn weight(&self, pool: RatingPool, rating: u32) -> u64 {
+        let distance = (rating as i64 - self.target(pool) as i64).unsigned_abs();
+        (self.band() as u64 + 1).saturating_sub(distance)
+    }
+
+    pub fn record_result(&mut self, pool: RatingPool, opponent_rating: u32, outcome: GameOutcome) {
+        let initial_rating = self.initial_rating;
+        let estimate = self
+            .estimates
+            .entry(pool)
+            .or_insert_with(|| Estimate { rating: initial_rating, ..Default::default() });
+
+        let expected = 1.0 / (1.0 + 10f32.powf((opponent_rating as f32 - estimate.rating) / 400.0));
+        estimate.rating =
+            (estimate.rating + K_FACTOR * (outcome.score() - expected)).max(MIN_ESTIMATE);
+
+        estimate.results.push_back(outcome);
+        while estimate.results.len() > RECENT_RESULTS {
+            estimate.results.pop_front();
+        }
+        self.widening = 0;
+
+        let (low, high) = self.target_range(pool);
+        log::info!(
+            "[Difficulty] {:?} against {} at {:?} - estimate now {}, targeting {}-{}",
+            outcome,
+            opponent_rating,
+            pool,
+            self.estimate(pool),
+            low,
+            high
+        );
+    }
+
+    /// Widens the band after an opponent fails to accept. Returns false if it's already maxed out.
+    pub fn widen(&mut self) -> bool {
+        if self.band() >= self.max_band {
+            return false;
+        }
+
+        self.widening += self.band_step;
+        log::info!("[Difficulty] Widened band to {}", self.band());
+        true
+    }
+
+    /// Draws count as half a win.
+    pub fn win_rate(&self, pool: RatingPool) -> Option<f32> {
+        let results = &self.estimates.get(&pool)?.results;
+        if results.is_empty() {
+            return None;
+        }
+
+        let score: f32 = results.iter().map(GameOutcome::score).sum();
+        (score / results.len() as f32).into()
+    }
+}
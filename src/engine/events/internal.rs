@@ -1,7 +1,10 @@
 use crossbeam_channel::{Receiver, Sender};
 
 use crate::lichess::action::Action as LichessAction;
+use crate::lichess::challenge::{ChallengeFailure, ChallengeOrigin};
 use crate::lichess::game::GameId;
+use crate::lichess::game::GameOutcome;
+use crate::lichess::tv::TvGame;
 use crate::stream::audio::Clip;
 use crate::twitch::action::Action as TwitchAction;
 use crate::twitch::events::ChatCommand;
@@ -35,11 +38,21 @@ pub enum Action {
 #[derive(Debug)]
 pub enum Notification {
     ChatCommand(ChatCommand),
-    VotingFinished,
+    VotingFinished { game_id: GameId },
     OutboundChallengeNullified,
+    SeekExpired,
+    OutboundChallengeUnanswered { failure: ChallengeFailure, origin: ChallengeOrigin },
+    InboundQueueChanged,
     GameVotesChanged,
+    GameVotesTick { game_id: GameId },
+    LobbyTick,
+    LobbyClosed,
+    RematchTick,
+    RematchClosed,
+    CorrespondenceTick,
+    TvUpdate { game: TvGame },
     SettingsChanged,
-    ChallengeSent { id: String, rating: u32 },
+    ChallengeSent { id: String, rating: u32, origin: ChallengeOrigin },
     Game(GameNotification),
 }
 
@@ -49,6 +62,7 @@ pub enum GameNotification {
     GameStarted { game_id: GameId },
     GameAbortable { game_id: GameId },
     GameFinished,
+    GameOutcome { game_id: GameId, outcome: Option<GameOutcome> },
     OurTurn { game_id: GameId },
     TheirTurn { game_id: GameId },
     PlayerMoved { game_id: GameId, was_us: bool },
@@ -5,7 +5,11 @@ use crate::{
     lichess::game::Game,
     stream::{
         audio::Clip,
-        model::{Command, GameVotes, Notice, Side, State, Timer},
+        model::{
+            Command, CorrespondencePanel, GameChatLine, GameVotes, HeadToHead, LobbyView, Notice,
+            OpponentCard, RematchView, SessionRating, Side, SimulPanel, SpectatedGame, State,
+            Timer,
+        },
     },
 };
 
@@ -29,6 +33,16 @@ pub enum Notification {
     State { state: State },
     Settings { settings: Settings },
     GameVotes { votes: GameVotes },
+    Simul { panels: Vec<SimulPanel> },
+    HeadToHead { record: Option<HeadToHead> },
+    OpponentCard { card: Option<OpponentCard> },
+    Lobby { lobby: Option<LobbyView> },
+    ChallengeQueue { queue: Vec<String> },
+    Correspondence { games: Vec<CorrespondencePanel> },
+    Rematch { rematch: Option<RematchView> },
+    GameChat { lines: Vec<GameChatLine> },
+    OurRating { rating: Option<SessionRating> },
+    Spectating { game: Option<SpectatedGame> },
     GameUpdate(GameUpdate),
 }
 
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::lichess::game::{Game, GameId};
+
+/// A game waiting on chat's move counts for this much more than one waiting on the opponent.
+const OUR_TURN_WEIGHT: f32 = 3.0;
+/// Clock urgency is this over chat's seconds left, so it climbs steeply as time runs out.
+const CLOCK_URGENCY_SECONDS: f32 = 60.0;
+/// A game gains a point of urgency for each this many seconds it's gone unwatched.
+const UNWATCHED_SECONDS_PER_POINT: f32 = 60.0;
+
+/// Decides which game chat watches when several are running - the one that most needs them,
+/// but only once the game on stream has been watched for a while so it isn't jumping around.
+pub struct FocusPolicy {
+    min_dwell: Duration,
+    focused_at: Option<Instant>,
+    /// When each game was last on stream.
+    last_viewed: HashMap<GameId, Instant>,
+}
+
+impl FocusPolicy {
+    pub fn new(config: &config::Focus) -> Self {
+        Self {
+            min_dwell: Duration::from_secs(config.min_dwell_seconds),
+            focused_at: None,
+            last_viewed: Default::default(),
+        }
+    }
+
+    /// The game to move focus to, if it should move at all.
+    pub fn next_game(&self, games: &[&Game], current_game_id: Option<&str>) -> Option<GameId> {
+        let has_dwelt = self.focused_at.map(|at| at.elapsed() >= self.min_dwell).unwrap_or(true);
+        if current_game_id.is_some() && !has_dwelt {
+            return None;
+        }
+
+        let game = games.iter().max_by(|l, r| self.urgency(l).total_cmp(&self.urgency(r)))?;
+        (Some(game.game_id.as_str()) != current_game_id).then(|| game.game_id.to_string())
+    }
+
+    pub fn record_switch(&mut self, from_game_id: Option<&str>, to_game_id: &str) {
+        let now = Instant::now();
+        if let Some(from_game_id) = from_game_id {
+            self.last_viewed.insert(from_game_id.to_string(), now);
+        }
+
+        self.last_viewed.insert(to_game_id.to_string(), now);
+        self.focused_at = now.into();
+    }
+
+    pub fn remove_game(&mut self, game_id: &str) {
+        self.last_viewed.remove(game_id);
+    }
+
+    /// Higher for games where it's chat's turn, chat's clock is low, or chat hasn't looked
+    /// in a while.
+    fn urgency(&self, game: &Game) -> f32 {
+        let turn = if game.is_our_turn { OUR_TURN_WEIGHT } else { 0.0 };
+
+        let seconds_left = game.us.timer.remaining().as_secs_f32();
+        let clock = CLOCK_URGENCY_SECONDS / (seconds_left + 1.0);
+
+        let last_viewed = self.last_viewed.get(&game.game_id).copied();
+        let unwatched = last_viewed.unwrap_or(game.timestamp).elapsed();
+        let unwatched = unwatched.as_secs_f32() / UNWATCHED_SECONDS_PER_POINT;
+
+        turn + clock + unwatched
+    }
+}
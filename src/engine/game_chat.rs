@@ -0,0 +1,71 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::config;
+use crate::lichess::game::GameId;
+use crate::stream::model::GameChatLine;
+
+/// How many lines are kept for each game.
+const MAX_LINES: usize = 20;
+
+/// Bridges the Lichess game chat - the opponent and spectators are shown on stream, and
+/// the bot greets them and says good game.
+pub struct GameChat {
+    enabled: bool,
+    our_id: String,
+    greeting: Option<String>,
+    farewell: Option<String>,
+    post_votes: bool,
+    lines: HashMap<GameId, VecDeque<GameChatLine>>,
+}
+
+impl GameChat {
+    pub fn new(config: &config::GameChat, our_id: &str, channel: &str) -> Self {
+        let fill = |message: &Option<String>| {
+            message.as_ref().map(|message| message.replace("{channel}", channel))
+        };
+
+        Self {
+            enabled: config.enabled,
+            our_id: our_id.to_lowercase(),
+            greeting: fill(&config.greeting),
+            farewell: fill(&config.farewell),
+            post_votes: config.enabled && config.post_votes,
+            lines: Default::default(),
+        }
+    }
+
+    /// Keeps a line from the game's chat. Returns false for our own lines, which aren't shown.
+    pub fn record(&mut self, game_id: &str, username: String, text: String) -> bool {
+        if !self.enabled || username.to_lowercase() == self.our_id {
+            return false;
+        }
+
+        let lines = self.lines.entry(game_id.to_string()).or_default();
+        lines.push_back(GameChatLine { username, text });
+        while lines.len() > MAX_LINES {
+            lines.pop_front();
+        }
+
+        true
+    }
+
+    pub fn lines(&self, game_id: &str) -> Vec<GameChatLine> {
+        self.lines.get(game_id).map(|lines| lines.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn remove_game(&mut self, game_id: &str) {
+        self.lines.remove(game_id);
+    }
+
+    pub fn greeting(&self) -> Option<String> {
+        self.greeting.clone().filter(|_| self.enabled)
+    }
+
+    pub fn farewell(&self) -> Option<String> {
+        self.farewell.clone().filter(|_| self.enabled)
+    }
+
+    pub fn posts_votes(&self) -> bool {
+        self.post_votes
+    }
+}
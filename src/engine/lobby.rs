@@ -0,0 +1,179 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use lichess_api::model::VariantKey;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::config;
+use crate::engine::events::internal::{EventSender, Notification};
+use crate::engine::positions::StartingPosition;
+use crate::engine::votes::Username;
+use crate::lichess::challenge::ChallengeOrigin;
+use crate::stream::model::{LobbyCandidate, LobbyView};
+
+/// Between games, chat picks the next opponent from a handful of online bots.
+pub struct Lobby {
+    enabled: bool,
+    candidate_count: usize,
+    vote_duration: Duration,
+    candidates: Vec<Candidate>,
+    votes: HashMap<Username, usize>,
+    runners_up: VecDeque<Candidate>,
+    timer: Option<LobbyTimer>,
+    event_sender: EventSender,
+}
+
+struct LobbyTimer {
+    start: Instant,
+    timer_handle: JoinHandle<()>,
+}
+
+/// An opponent, usually a bot, along with the time control it would be challenged at.
+#[derive(Clone)]
+pub struct Candidate {
+    pub id: String,
+    pub name: String,
+    /// The rating for the time control's speed.
+    pub rating: u32,
+    pub time_control: config::TimeControl,
+    pub variant: VariantKey,
+    pub position: Option<StartingPosition>,
+    pub origin: ChallengeOrigin,
+}
+
+impl Candidate {
+    /// The time control, along with anything unusual about the game, e.g. "960 5+3".
+    pub fn description(&self) -> String {
+        let label = self.time_control.label();
+        match (&self.position, &self.variant) {
+            (Some(position), _) => format!("{} {}", position.key, label),
+            (None, VariantKey::Chess960) => format!("960 {}", label),
+            _ => label,
+        }
+    }
+}
+
+impl Lobby {
+    pub fn new(config: &config::Lobby, event_sender: EventSender) -> Self {
+        Self {
+            enabled: config.enabled,
+            candidate_count: config.candidates.clamp(3, 5),
+            vote_duration: Duration::from_secs(config.vote_seconds),
+            candidates: Default::default(),
+            votes: Default::default(),
+            runners_up: Default::default(),
+            timer: None,
+            event_sender,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.candidates.is_empty()
+    }
+
+    pub fn candidate_count(&self) -> usize {
+        self.candidate_count
+    }
+
+    pub fn open(&mut self, candidates: Vec<Candidate>) {
+        self.clear();
+        self.candidates = candidates;
+
+        let mut event_sender = self.event_sender.clone();
+        let vote_duration = self.vote_duration;
+
+        let timer_handle = tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            for _ in 0..vote_duration.as_secs() {
+                interval.tick().await;
+                event_sender.send_notification(Notification::LobbyTick);
+            }
+            event_sender.send_notification(Notification::LobbyClosed);
+        });
+
+        self.timer = LobbyTimer { start: Instant::now(), timer_handle }.into();
+        log::info!("[Lobby] Opened with {} candidates", self.candidates.len());
+    }
+
+    /// Picks are numbered from 1 as shown on stream. Returns false for an invalid pick.
+    pub fn vote(&mut self, user: Username, pick: usize) -> bool {
+        if pick == 0 || pick > self.candidates.len() {
+            return false;
+        }
+
+        self.votes.insert(user, pick - 1);
+        true
+    }
+
+    /// Closes voting, queueing the candidates from most to least popular.
+    pub fn close(&mut self) {
+        let tally = self.tally();
+        let mut ranked: Vec<(usize, Candidate)> =
+            std::mem::take(&mut self.candidates).into_iter().enumerate().collect();
+        // Stable, so ties go to whoever was listed first.
+        ranked.sort_by_key(|(index, _)| std::cmp::Reverse(tally[*index]));
+
+        self.runners_up = ranked.into_iter().map(|(_, candidate)| candidate).collect();
+        self.votes.clear();
+        self.timer = None;
+
+        if let Some(winner) = self.runners_up.front() {
+            log::info!("[Lobby] Chat picked {}", &winner.name);
+        }
+    }
+
+    /// The next candidate to challenge - the winner first, then the runners up.
+    pub fn next_candidate(&mut self) -> Option<Candidate> {
+        self.runners_up.pop_front()
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(timer) = &self.timer {
+            timer.timer_handle.abort();
+        }
+
+        self.candidates.clear();
+        self.votes.clear();
+        self.runners_up.clear();
+        self.timer = None;
+    }
+
+    pub fn view(&self) -> Option<LobbyView> {
+        if !self.is_open() {
+            return None;
+        }
+
+        let seconds_remaining = match &self.timer {
+            Some(timer) => self.vote_duration.saturating_sub(timer.start.elapsed()).as_secs(),
+            None => 0,
+        };
+
+        let tally = self.tally();
+        let candidates = self
+            .candidates
+            .iter()
+            .zip(tally)
+            .map(|(candidate, votes)| LobbyCandidate {
+                name: candidate.name.to_string(),
+                rating: candidate.rating,
+                time_control: candidate.description(),
+                votes,
+            })
+            .collect();
+
+        LobbyView { seconds_remaining, candidates }.into()
+    }
+
+    fn tally(&self) -> Vec<u32> {
+        let mut tally = vec![0; self.candidates.len()];
+        for pick in self.votes.values() {
+            tally[*pick] += 1;
+        }
+        tally
+    }
+}
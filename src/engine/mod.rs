@@ -1,16 +1,40 @@
+pub mod abort;
+pub mod archive;
+pub mod correspondence;
+pub mod difficulty;
 pub mod events;
+pub mod focus;
+pub mod game_chat;
+pub mod lobby;
+pub mod opponents;
+pub mod positions;
+pub mod profiles;
+pub mod rating;
+pub mod rematch;
+pub mod reputation;
+pub mod simul;
+pub mod teams;
+pub mod viewers;
 pub mod votes;
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 
 use lichess_api::model::users::User;
 use lichess_api::model::Speed;
+use lichess_api::model::Title;
+use lichess_api::model::VariantKey;
 
-use rand::prelude::Distribution;
 use rand::rngs::ThreadRng;
 use rand::seq::IteratorRandom;
 use rand::seq::SliceRandom;
+use tokio::task::JoinHandle;
 
+use crate::config::AccountMode;
+use crate::config::Config;
+use crate::config::TimeControl;
 use crate::error::Result;
 
 use crate::engine::events::external;
@@ -22,26 +46,49 @@ use crate::lichess::action::Action as LichessAction;
 use crate::lichess::action::Actor as LichessActor;
 use crate::lichess::action::GameAction;
 use crate::lichess::challenge::ChallengeManager;
+use crate::lichess::challenge::ChallengeOrigin;
 use crate::lichess::events::Event as LichessEvent;
 use crate::lichess::game::GameManager;
+use crate::lichess::tv::TvGame;
 use crate::lichess::Context as LichessContext;
 
 use crate::stream::audio::Clip;
 use crate::stream::model::Command;
+use crate::stream::model::Notice;
 
 use crate::stream::model::Side;
+use crate::stream::model::SimulPanel;
 use crate::stream::model::State;
 use crate::twitch::action::Action as TwitchAction;
 use crate::twitch::command::Command as TwitchCommand;
+use crate::twitch::command::GameMode;
 use crate::twitch::command::Setting;
 use crate::twitch::events::ChatCommand;
 use crate::twitch::events::Event as TwitchEvent;
 use crate::twitch::Context as TwitchContext;
 
+use self::abort::AbortPolicy;
+use self::archive::PgnArchive;
+use self::correspondence::Correspondence;
+use self::difficulty::{DifficultyController, RatingPool};
 use self::events::internal::Action;
 use self::events::internal::GameNotification;
 use self::events::internal::Notification;
+use self::focus::FocusPolicy;
+use self::game_chat::GameChat;
+use self::lobby::{Candidate, Lobby};
+use self::opponents::OpponentSelector;
+use self::positions::{PositionLibrary, StartingPosition};
+use self::profiles::OpponentProfiles;
+use self::rating::RatingTracker;
+use self::rematch::{Offer, Rematch};
+use self::reputation::Reputation;
+use self::simul::Simul;
+use self::teams::{TeamMode, TeamResult};
+use self::viewers::{ViewerEntry, ViewerQueue};
+use self::votes::audit::{CloseReason, GameAudit};
 use self::votes::game::Vote;
+use self::votes::game::VoteTracker;
 
 pub struct Engine {
     game_votes: self::votes::game::VoteTracker,
@@ -51,9 +98,32 @@ pub struct Engine {
     stream_events: stream::EventSender,
     challenge_manager: ChallengeManager,
     game_manager: GameManager,
+    simul: Option<Simul>,
+    teams: TeamMode,
+    difficulty: DifficultyController,
+    opponents: OpponentSelector,
+    reputation: Reputation,
+    /// Where each game's opponent came from, for games started from a challenge we know of.
+    game_origins: HashMap<String, ChallengeOrigin>,
+    profiles: OpponentProfiles,
+    lobby: Lobby,
+    viewers: ViewerQueue,
+    positions: PositionLibrary,
+    correspondence: Correspondence,
+    rematch: Rematch,
+    game_chat: GameChat,
+    ratings: RatingTracker,
+    archive: PgnArchive,
+    aborts: AbortPolicy,
+    focus: FocusPolicy,
+    /// The latest from Lichess TV, shown while there's nothing of our own to watch.
+    tv_game: Option<TvGame>,
+    /// Holds our seek in the lobby open, in the Board API account mode.
+    seek: Option<JoinHandle<()>>,
     lichess_actor: LichessActor,
     is_running: bool,
     rng: ThreadRng,
+    config: Config,
 }
 
 impl Engine {
@@ -61,34 +131,90 @@ impl Engine {
         stream_events: stream::EventSender,
         lichess_context: LichessContext,
         twitch_context: TwitchContext,
+        config: Config,
     ) -> Self {
         let our_id = lichess_context.our_id.to_string();
         let internal_queue = internal::EventQueue::default();
         internal_queue.event_sender().send_action(Action::FindNewGame);
+        internal_queue.event_sender().send_notification(Notification::SettingsChanged);
+        internal_queue.event_sender().send_action(LichessAction::refresh_our_rating().into());
+
+        let simul = config
+            .simul
+            .enabled
+            .then(|| Simul::new(config.simul.boards, internal_queue.event_sender()));
+        let max_games = simul.as_ref().map(|_| config.simul.boards).unwrap_or(1);
+        let teams = TeamMode::new(
+            config.teams.enabled,
+            config.teams.lichess_games_between,
+            internal_queue.event_sender(),
+        );
+        let reputation_path = PathBuf::from(&config.storage.directory).join("reputation.json");
+        let correspondence_path =
+            PathBuf::from(&config.storage.directory).join("correspondence.json");
+        let game_chat = GameChat::new(&config.game_chat, &our_id, &config.twitch.channel);
+        let archive_path = PathBuf::from(&config.storage.directory).join("games.pgn");
+        let archive = PgnArchive::new(archive_path, &our_id, &config.twitch.channel);
+        let mut game_votes = VoteTracker::new(&Speed::Blitz, internal_queue.event_sender());
+        game_votes.set_spectator_weight(config.spectators.weight);
 
         Engine {
-            game_votes: self::votes::game::VoteTracker::new(
-                &Speed::Blitz,
+            game_votes,
+            settings_votes: self::votes::settings::VoteTracker::new(
+                &config.time_controls.catalogue,
                 internal_queue.event_sender(),
             ),
-            settings_votes: self::votes::settings::VoteTracker::new(internal_queue.event_sender()),
             external_events: external::EventManager::new(lichess_context.clone(), twitch_context),
             stream_events,
             challenge_manager: ChallengeManager::new(
                 our_id.to_string(),
+                config.inbound.clone(),
                 internal_queue.event_sender(),
             ),
-            game_manager: GameManager::new(our_id, internal_queue.event_sender()),
+            game_manager: GameManager::new(our_id, max_games, internal_queue.event_sender()),
+            simul,
+            teams,
+            difficulty: DifficultyController::new(&config.difficulty),
+            opponents: OpponentSelector::new(&config.opponents),
+            reputation: Reputation::load(reputation_path),
+            game_origins: Default::default(),
+            profiles: Default::default(),
+            lobby: Lobby::new(&config.lobby, internal_queue.event_sender()),
+            viewers: ViewerQueue::new(config.viewers.enabled, config.viewers.max_queue),
+            positions: PositionLibrary::load(Path::new(&config.positions.path)),
+            correspondence: Correspondence::load(
+                &config.correspondence,
+                correspondence_path,
+                internal_queue.event_sender(),
+            ),
+            rematch: Rematch::new(&config.rematch, internal_queue.event_sender()),
+            game_chat,
+            ratings: Default::default(),
+            archive,
+            aborts: AbortPolicy::new(&config.abort, internal_queue.event_sender()),
+            focus: FocusPolicy::new(&config.focus),
+            tv_game: None,
+            seek: None,
             internal_queue,
             lichess_actor: LichessActor::new(lichess_context),
             is_running: true,
             rng: rand::thread_rng(),
+            config,
         }
     }
 
     pub async fn setup(&mut self) -> Result<()> {
         self.external_events.subscribe_to_all().await?;
 
+        if self.config.tv.enabled {
+            crate::lichess::tv::follow(
+                &self.lichess_actor.context,
+                self.config.tv.recording.as_ref().map(PathBuf::from),
+                Duration::from_millis(self.config.tv.replay_millis),
+                self.internal_queue.event_sender(),
+            );
+        }
+
         // Wait a short amount of time for events to arrive.
         tokio::time::sleep(Duration::from_secs(3)).await;
 
@@ -96,35 +222,15 @@ impl Engine {
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        let mut now = tokio::time::Instant::now();
-
         while self.is_running {
-            self.process(&mut now).await?;
+            self.process().await?;
         }
 
         Ok(())
     }
 
-    pub async fn process(&mut self, now: &mut tokio::time::Instant) -> Result<()> {
-        // Update clock timers.
-        // Would normally use events - but this way avoids log spam.
-        if now.elapsed() > Duration::from_millis(1000) {
-            self.game_manager.advance_clocks(now.elapsed());
-            *now = tokio::time::Instant::now();
-
-            if let Some(current_game) = self.game_manager.current_game() {
-                let (side, timer) = if current_game.is_our_turn {
-                    (Side::Ours, current_game.us.timer)
-                } else {
-                    (Side::Theirs, current_game.opponent.timer)
-                };
-
-                let game_update = stream::GameUpdate::Timer { side, timer };
-                let notification = stream::Notification::GameUpdate(game_update);
-                _ = self.stream_events.send(stream::Event::Notification(notification));
-            }
-        }
-
+    /// Clocks aren't updated here - the stream counts them down between moves.
+    pub async fn process(&mut self) -> Result<()> {
         // Check for errors as well and ensure we can recover from a broken or ended stream.
         if let Ok(Some(event)) = self.external_events.next_event() {
             log::info!("External event: {event:?}");
@@ -168,7 +274,7 @@ impl Engine {
                 _ = self.stream_events.send(stream::Event::Action(action));
             }
             Action::FindNewGame => self.find_new_game().await,
-            Action::SwitchGame(game) => self.game_manager.switch_game(&game),
+            Action::SwitchGame(game) => self.switch_game(&game),
             Action::Shutdown => self.is_running = false,
         }
     }
@@ -180,77 +286,281 @@ impl Engine {
                 let notification = stream::Notification::ChatCommand { command };
                 _ = self.stream_events.send(stream::Event::Notification(notification));
             }
-            Notification::OutboundChallengeNullified => {
-                if self.game_manager.current_game().is_none() {
+            Notification::TvUpdate { game } => {
+                self.tv_game = game.into();
+                self.send_spectating();
+            }
+            Notification::OutboundChallengeNullified | Notification::SeekExpired => {
+                if self.simul.is_some() || self.game_manager.current_game().is_none() {
+                    self.internal_queue.event_sender().send_action(Action::FindNewGame);
+                }
+            }
+            Notification::InboundQueueChanged => {
+                self.send_challenge_queue();
+
+                // Also sent as a challenge is taken off the queue to accept, but is_idle stays
+                // false until its game starts.
+                if self.is_idle() && !self.challenge_manager.inbound().is_empty() {
                     self.internal_queue.event_sender().send_action(Action::FindNewGame);
                 }
             }
+            Notification::OutboundChallengeUnanswered { failure, origin } => {
+                if origin != ChallengeOrigin::Bot {
+                    log::info!("{:?} challenge unanswered: {:?}", origin, failure);
+                    return;
+                }
+
+                self.reputation.record_unanswered(failure);
+                self.difficulty.widen();
+            }
             Notification::GameVotesChanged => {
-                let votes = self.game_votes.game_votes();
+                let current_game_id = match self.teams.side_to_move() {
+                    Some(color) => self.teams.game_id(color).into(),
+                    None => self.game_manager.current_game().map(|game| game.game_id.to_string()),
+                };
+                let votes = match current_game_id {
+                    Some(game_id) => self.vote_tracker(&game_id).game_votes(),
+                    None => self.game_votes.game_votes(),
+                };
                 let notification = stream::Notification::GameVotes { votes };
                 _ = self.stream_events.send(stream::Event::Notification(notification));
+
+                self.send_simul_update();
+            }
+            Notification::GameVotesTick { game_id } => {
+                self.vote_tracker(&game_id).record_history();
+                self.internal_queue
+                    .event_sender()
+                    .send_notification(Notification::GameVotesChanged);
             }
             Notification::SettingsChanged => {
                 let settings = self.settings_votes.settings();
                 let notification = stream::Notification::Settings { settings };
                 _ = self.stream_events.send(stream::Event::Notification(notification));
             }
-            Notification::ChallengeSent { id, rating } => {
+            Notification::LobbyTick => self.send_lobby(),
+            Notification::RematchTick => self.send_rematch(),
+            Notification::RematchClosed => {
+                let action = if self.rematch.close() {
+                    Action::Lichess(LichessAction::challenge_random_bot())
+                } else {
+                    Action::FindNewGame
+                };
+                self.internal_queue.event_sender().send_action(action);
+                self.send_rematch();
+            }
+            Notification::CorrespondenceTick => {
+                let game_manager = &self.game_manager;
+                self.correspondence.retain(|game_id| game_manager.game(game_id).is_some());
+
+                for game_id in self.correspondence.due_games() {
+                    let action = Action::Lichess(LichessAction::make_move(game_id));
+                    self.internal_queue.event_sender().send_action(action);
+                }
+                self.send_correspondence();
+            }
+            Notification::LobbyClosed => {
+                self.lobby.close();
+                self.send_lobby();
+                self.internal_queue
+                    .event_sender()
+                    .send_action(Action::Lichess(LichessAction::challenge_random_bot()));
+            }
+            Notification::ChallengeSent { id, rating, origin } => {
+                self.challenge_manager.record_outbound_origin(origin);
+                if origin == ChallengeOrigin::Bot {
+                    self.reputation.record_challenge(&id);
+                }
                 let notification =
                     stream::Notification::State { state: State::ChallengingUser { id, rating } };
                 _ = self.stream_events.send(stream::Event::Notification(notification));
             }
-            Notification::VotingFinished => {
-                if let Some(Vote::Delay) = self.game_votes.get_top_vote() {
-                    self.game_votes.enable();
+            Notification::VotingFinished { game_id } => {
+                let votes = self.vote_tracker(&game_id);
+                if let Some(Vote::Delay) = votes.get_top_vote() {
+                    votes.enable();
                 } else {
-                    self.game_votes.disable();
+                    votes.disable();
                 }
             }
             Notification::Game(notification) => match notification {
                 GameNotification::NewCurrentGame => {
+                    self.send_spectating();
                     self.game_votes.enable();
                     self.game_votes.reset();
+                    self.send_notice(Notice::default());
 
                     if let Some(game) = self.game_manager.current_game() {
+                        self.game_votes.set_speed(&game.speed);
+                        self.game_votes.set_game(game.game_id.to_string());
+
                         let notification = stream::Notification::ActiveGame { game: game.clone() };
                         _ = self.stream_events.send(stream::Event::Notification(notification));
 
                         let action = stream::Action::PlayClip { clip: Clip::Start };
                         _ = self.stream_events.send(stream::Event::Action(action))
                     }
+
+                    let game_id = self.game_manager.current_game().map(|game| game.game_id.clone());
+                    self.send_head_to_head(game_id.as_deref());
+                    self.send_opponent_card(game_id.as_deref());
+                    self.send_game_chat(game_id.as_deref());
+                    self.send_our_rating();
                 }
                 GameNotification::GameStarted { game_id } => {
-                    self.challenge_manager.cancel_outbound();
+                    self.challenge_manager.game_started(&game_id);
+                    self.send_spectating();
+                    // Games already underway were greeted before a restart.
+                    let game = self.game_manager.game(&game_id);
+                    let is_new = game.map(|game| game.move_count() == 0);
+                    if let (Some(greeting), Some(true)) = (self.game_chat.greeting(), is_new) {
+                        let action = LichessAction::write_chat(game_id.to_string(), greeting);
+                        self.internal_queue.event_sender().send_action(Action::Lichess(action));
+                    }
+
+                    // Correspondence games are played alongside everything else.
+                    if let Some(game) = self.game_manager.game(&game_id) {
+                        if game.is_correspondence() {
+                            self.correspondence.add_game(&game_id, &game.opponent.name);
+                            self.send_correspondence();
 
-                    if let Some(game) = &self.game_manager.current_game() {
-                        if game.game_id == game_id {
+                            // Its challenge was cleared above, but the stream still needs a game.
+                            self.cancel_seek();
+                            self.internal_queue.event_sender().send_action(Action::FindNewGame);
                             return;
                         }
+
+                        self.aborts.watch(game);
+
+                        if self.profiles.should_fetch(&game.opponent.name) {
+                            let username = game.opponent.name.to_string();
+                            let action = LichessAction::fetch_opponent_profile(username);
+                            self.internal_queue.event_sender().send_action(action.into());
+                        }
+                    }
+
+                    self.cancel_seek();
+                    self.lobby.clear();
+                    self.send_lobby();
+                    self.rematch.clear();
+                    self.send_rematch();
+
+                    let game = self.game_manager.game(&game_id);
+                    let variant = game.map(|game| game.variant.clone());
+                    if let Some(VariantKey::FromPosition) = variant {
+                        self.settings_votes.clear_position_votes();
+                    }
+
+                    let current_game_id =
+                        self.game_manager.current_game().map(|game| game.game_id.to_string());
+
+                    if current_game_id.as_ref() == Some(&game_id) {
+                        return;
                     }
 
                     let mut event_sender = self.internal_queue.event_sender();
 
-                    event_sender.send_action(Action::SwitchGame(game_id.to_string()));
+                    if let Some(simul) = &mut self.simul {
+                        if let Some(game) = self.game_manager.game(&game_id) {
+                            _ = simul.add_game(game_id.to_string(), &game.speed);
+                        }
+                        if let Some(votes) = simul.votes_mut(&game_id) {
+                            votes.set_spectator_weight(self.config.spectators.weight);
+                        }
 
-                    tokio::task::spawn(async move {
-                        tokio::time::sleep(Duration::from_secs(30)).await;
-                        event_sender.send_notification(Notification::Game(
-                            GameNotification::GameAbortable { game_id },
-                        ));
-                    });
+                        // Keep challenging until every board is taken.
+                        event_sender.send_action(Action::FindNewGame);
+                        self.send_simul_update();
+                    }
+
+                    // A game already on stream keeps focus until something is more urgent.
+                    if current_game_id.is_none() {
+                        event_sender.send_action(Action::SwitchGame(game_id.to_string()));
+                    }
                 }
                 GameNotification::GameAbortable { game_id } => {
-                    // Attempt to abort the game.
+                    let Some(game) = self.game_manager.game(&game_id) else {
+                        return;
+                    };
+                    let should_abort = self.aborts.should_abort(game);
+                    self.aborts.remove_game(&game_id);
+                    if !should_abort {
+                        return;
+                    }
+
+                    log::info!("Aborting game {} - the opponent never moved", &game_id);
+                    self.reputation.record_no_show(&game_id);
                     let action = Action::Lichess(LichessAction::abort(game_id));
                     self.internal_queue.event_sender().send_action(action);
                 }
+                GameNotification::GameOutcome { game_id, outcome } => {
+                    // Only games against bots say anything about bots or how strong chat is.
+                    let is_bot_game =
+                        self.game_origins.remove(&game_id) == Some(ChallengeOrigin::Bot);
+                    if is_bot_game {
+                        self.reputation.record_outcome(&game_id, outcome);
+                    }
+
+                    // Archived here rather than on the account stream's game finish, which can
+                    // arrive before the final state and leave the result unknown.
+                    let audit = self.write_vote_audit(&game_id);
+                    self.archive_game(&game_id, audit.as_ref());
+
+                    // Aborted games have no outcome, and there's nothing to say good game to.
+                    if let (Some(farewell), Some(_)) = (self.game_chat.farewell(), outcome) {
+                        let action = LichessAction::write_chat(game_id.to_string(), farewell);
+                        self.internal_queue.event_sender().send_action(Action::Lichess(action));
+                    }
+
+                    if self.correspondence.contains(&game_id) {
+                        self.correspondence.remove_game(&game_id);
+                        self.send_correspondence();
+                    }
+
+                    let is_displayed = match self.game_manager.current_game() {
+                        Some(game) => game.game_id == game_id,
+                        None => true,
+                    };
+                    if is_displayed {
+                        self.send_head_to_head(Some(&game_id));
+                    }
+
+                    let game = self.game_manager.game(&game_id);
+                    if let (Some(game), Some(outcome)) = (game, outcome) {
+                        if !game.is_correspondence() {
+                            self.rematch.record_result(&game.opponent.name, outcome);
+                        }
+                    }
+
+                    // Kept apart by speed and variant, as chat's strength at each can differ.
+                    let pool = game.map(|game| RatingPool::new(&game.speed, &game.variant));
+                    let opponent_rating = game.and_then(|game| game.opponent.rating);
+                    match (pool, opponent_rating, outcome) {
+                        _ if !is_bot_game => (),
+                        (Some(pool), Some(opponent_rating), Some(outcome)) => {
+                            self.difficulty.record_result(pool, opponent_rating, outcome)
+                        }
+                        (_, None, Some(_)) => {
+                            log::warn!("No opponent rating for game {} - not calibrating", game_id)
+                        }
+                        _ => (),
+                    }
+                }
                 GameNotification::GameFinished => {
                     if self.game_manager.current_game().is_some() {
                         return;
                     }
 
-                    self.internal_queue.event_sender().send_action(Action::FindNewGame);
+                    // Chat gets a chance to play the same opponent again before moving on.
+                    let rematch_opened = match (&self.simul, self.game_manager.last_game()) {
+                        (None, Some(last_game)) => self.rematch.open(last_game),
+                        _ => false,
+                    };
+                    if rematch_opened {
+                        self.send_rematch();
+                    } else {
+                        self.internal_queue.event_sender().send_action(Action::FindNewGame);
+                    }
 
                     if let Some(last_game) = self.game_manager.last_game() {
                         let notification =
@@ -262,13 +572,31 @@ impl Engine {
                     _ = self.stream_events.send(stream::Event::Notification(notification));
                 }
                 GameNotification::OurTurn { game_id } => {
+                    if self.correspondence.contains(&game_id) {
+                        let timer = self.game_manager.game(&game_id).map(|game| game.us.timer);
+                        let time_left = timer.map(|timer| timer.remaining()).unwrap_or_default();
+                        self.correspondence.start_turn(&game_id, time_left);
+                        self.send_correspondence();
+                        return;
+                    }
+
+                    let simul_votes =
+                        self.simul.as_mut().and_then(|simul| simul.votes_mut(&game_id));
+                    if let Some(votes) = simul_votes {
+                        votes.enable();
+                        votes.schedule_action_vote(game_id.to_string());
+                    }
+
                     let Some(game) = self.game_manager.current_game() else {
                         return;
                     };
 
-                    self.game_votes.enable();
+                    if game.game_id != game_id {
+                        return;
+                    }
 
-                    if game.game_id == game_id {
+                    if self.simul.is_none() {
+                        self.game_votes.enable();
                         self.game_votes.schedule_action_vote(game_id);
                     }
 
@@ -276,19 +604,35 @@ impl Engine {
                     _ = self.stream_events.send(stream::Event::Notification(notification));
                 }
                 GameNotification::TheirTurn { game_id } => {
+                    if self.correspondence.contains(&game_id) {
+                        self.correspondence.end_turn(&game_id);
+                        self.send_correspondence();
+                        return;
+                    }
+
                     // Not sure if we really need to do anything here?
                     log::info!("Opponents turn in game {}", game_id);
                     let notification = stream::Notification::State { state: State::TheirTurn };
                     _ = self.stream_events.send(stream::Event::Notification(notification));
                 }
                 GameNotification::PlayerMoved { game_id, was_us } => {
+                    if let Some(game) = self.game_manager.game(&game_id) {
+                        self.aborts.record_move(game, was_us);
+                    }
+                    self.send_simul_update();
+
+                    // Every board has its own votes, so focus can move mid-game.
+                    if self.simul.is_some() {
+                        self.refocus();
+                    }
+
                     // If we moved, we can use this opportunity to switch to another game.
                     let Some(current_game) = self.game_manager.current_game() else {
                         return;
                     };
                     if game_id == current_game.game_id {
                         let game_update =
-                            stream::GameUpdate::Board { board: current_game.board.clone() };
+                            stream::GameUpdate::Board { board: current_game.position.board };
                         let notification = stream::Notification::GameUpdate(game_update);
                         _ = self.stream_events.send(stream::Event::Notification(notification));
 
@@ -298,16 +642,16 @@ impl Engine {
                         let notification = stream::Notification::GameUpdate(game_update);
                         _ = self.stream_events.send(stream::Event::Notification(notification));
 
-                        let side = if was_us { Side::Ours } else { Side::Theirs };
-                        let timer = if was_us {
-                            current_game.us.timer
-                        } else {
-                            current_game.opponent.timer
-                        };
-
-                        let game_update = stream::GameUpdate::Timer { side, timer };
-                        let notification = stream::Notification::GameUpdate(game_update);
-                        _ = self.stream_events.send(stream::Event::Notification(notification));
+                        // The mover's clock stops and the other side's starts.
+                        let timers = [
+                            (Side::Ours, current_game.us.timer),
+                            (Side::Theirs, current_game.opponent.timer),
+                        ];
+                        for (side, timer) in timers {
+                            let game_update = stream::GameUpdate::Timer { side, timer };
+                            let notification = stream::Notification::GameUpdate(game_update);
+                            _ = self.stream_events.send(stream::Event::Notification(notification));
+                        }
                     }
                 }
             },
@@ -318,7 +662,12 @@ impl Engine {
         match action {
             LichessAction::Account(action) => match action {
                 AccountAction::AcceptChallenge { challenge_id } => {
-                    _ = self.lichess_actor.accept_challenge(challenge_id).await;
+                    let result = self.lichess_actor.accept_challenge(challenge_id).await;
+                    if !matches!(result, Ok(true)) {
+                        log::warn!("Failed to accept challenge - finding another game");
+                        self.challenge_manager.accept_failed();
+                        self.internal_queue.event_sender().send_action(Action::FindNewGame);
+                    }
                 }
                 AccountAction::CancelChallenge { challenge_id } => {
                     _ = self.lichess_actor.cancel_challenge(challenge_id).await;
@@ -329,6 +678,15 @@ impl Engine {
                 AccountAction::ChallengeRandomBot => {
                     self.challenge_random_bot().await;
                 }
+                AccountAction::QueueViewer { viewer, username } => {
+                    self.queue_viewer(viewer, username).await;
+                }
+                AccountAction::RefreshOurRating => {
+                    self.refresh_our_rating().await;
+                }
+                AccountAction::FetchOpponentProfile { username } => {
+                    self.fetch_opponent_profile(username).await;
+                }
             },
             LichessAction::Game { game_id, action } => match action {
                 GameAction::Abort => {
@@ -343,12 +701,34 @@ impl Engine {
                 GameAction::Resign => {
                     _ = self.lichess_actor.resign(&game_id).await;
                 }
+                GameAction::WriteChat { text } => {
+                    _ = self.lichess_actor.write_chat(&game_id, &text).await;
+                }
             },
         }
     }
 
     async fn find_new_game(&mut self) {
-        if self.game_manager.current_game().is_none() {
+        if self.teams.is_playing() {
+            log::info!("Not finding a new game - a team game is being played.");
+            return;
+        }
+
+        if self.rematch.is_open() {
+            log::info!("Not finding a new game - chat is voting on a rematch.");
+            return;
+        }
+
+        if self.challenge_manager.is_accepting() {
+            log::info!("Not finding a new game - waiting for an accepted challenge to start.");
+            return;
+        }
+
+        if self.teams.is_due() && self.game_manager.active_game_count() == 0 {
+            self.start_team_game();
+        } else if self.simul.is_some() {
+            self.fill_simul();
+        } else if self.game_manager.current_game().is_none() {
             self.find_new_opponent();
         } else {
             log::warn!("Cannot find new game - already in a game.")
@@ -356,12 +736,15 @@ impl Engine {
     }
 
     pub fn find_new_opponent(&mut self) {
-        if let Some(game_id) = self.game_manager.oldest_game_id() {
-            self.game_manager.switch_game(&game_id);
-        } else {
+        if !self.refocus() {
             if self.challenge_manager.outbound().is_some() {
                 self.challenge_manager.cancel_outbound();
             }
+            self.cancel_seek();
+
+            if self.accept_inbound() {
+                return;
+            }
 
             self.internal_queue
                 .event_sender()
@@ -369,8 +752,399 @@ impl Engine {
         }
     }
 
+    /// Puts the most urgent game on stream if focus is due to move. Returns false if there
+    /// are no games to watch.
+    fn refocus(&mut self) -> bool {
+        let current_game_id = self.game_manager.current_game().map(|game| game.game_id.clone());
+        let games = self.game_manager.active_games();
+        if games.is_empty() {
+            return false;
+        }
+
+        if let Some(game_id) = self.focus.next_game(&games, current_game_id.as_deref()) {
+            self.switch_game(&game_id);
+        }
+        true
+    }
+
+    fn switch_game(&mut self, game_id: &str) {
+        let current_game_id = self.game_manager.current_game().map(|game| game.game_id.clone());
+        self.focus.record_switch(current_game_id.as_deref(), game_id);
+        self.game_manager.switch_game(game_id);
+    }
+
+    /// Accepts the longest waiting inbound challenge, if there is one.
+    fn accept_inbound(&mut self) -> bool {
+        let Some(challenge_id) = self.challenge_manager.next_inbound() else {
+            return false;
+        };
+
+        self.lobby.clear();
+        self.send_lobby();
+
+        let action = Action::Lichess(LichessAction::accept_challenge(challenge_id));
+        self.internal_queue.event_sender().send_action(action);
+        true
+    }
+
+    fn fill_simul(&mut self) {
+        if self.game_manager.current_game().is_none() {
+            self.refocus();
+        }
+
+        let Some(simul) = &self.simul else {
+            return;
+        };
+
+        if simul.is_full() {
+            log::info!("Not challenging - simul is full.");
+            return;
+        }
+
+        if self.accept_inbound() {
+            return;
+        }
+
+        if self.challenge_manager.outbound().is_some() {
+            log::info!("Not challenging - a challenge is pending.");
+            return;
+        }
+
+        self.internal_queue
+            .event_sender()
+            .send_action(LichessAction::challenge_random_bot().into());
+    }
+
+    fn send_simul_update(&mut self) {
+        let Some(simul) = &self.simul else {
+            return;
+        };
+
+        let panels = simul
+            .boards()
+            .iter()
+            .filter_map(|board| {
+                let game = self.game_manager.game(&board.game_id)?;
+                let votes = board.votes.game_votes();
+                Some(SimulPanel { label: board.label, game: game.clone(), votes })
+            })
+            .collect();
+
+        let notification = stream::Notification::Simul { panels };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn start_team_game(&mut self) {
+        self.challenge_manager.cancel_outbound();
+        self.lobby.clear();
+        self.send_lobby();
+        self.teams.start_game();
+        self.send_spectating();
+
+        self.send_team_game();
+        self.send_head_to_head(None);
+        self.send_opponent_card(None);
+        self.send_notice(self.teams.notice());
+
+        let action = stream::Action::PlayClip { clip: Clip::Start };
+        _ = self.stream_events.send(stream::Event::Action(action));
+
+        self.start_team_turn();
+    }
+
+    fn start_team_turn(&mut self) {
+        let Some(color) = self.teams.side_to_move() else {
+            return;
+        };
+
+        let game_id = self.teams.game_id(color);
+        let votes = self.vote_tracker(&game_id);
+        votes.enable();
+        votes.schedule_action_vote(game_id);
+
+        let notification = stream::Notification::State { state: State::TeamTurn { color } };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    /// Plays chat's top vote, or a random move if nobody voted before the deadline.
+    async fn make_correspondence_move(&mut self, game_id: String) {
+        let Some(game) = self.game_manager.game(&game_id) else {
+            return;
+        };
+
+        if !game.is_our_turn {
+            return;
+        }
+
+        let chess_move = match self.correspondence.top_vote(&game_id) {
+            Some(chess_move) if game.position.is_legal(chess_move) => chess_move,
+            _ => {
+                let Some(chess_move) = game.position.legal_moves().choose(&mut self.rng).copied()
+                else {
+                    return;
+                };
+                log::info!("Making random move {} in game {}", chess_move.to_string(), &game_id);
+                chess_move
+            }
+        };
+
+        if let Err(error) = self.lichess_actor.make_move(&game_id, chess_move).await {
+            log::error!("Make move error: {}", error.to_string());
+        }
+    }
+
+    fn make_team_move(&mut self, game_id: String) {
+        let Some(color) = self.teams.color_for_game_id(&game_id) else {
+            return;
+        };
+
+        if self.teams.side_to_move() != Some(color) {
+            log::warn!("Ignoring team move for {} - not their turn", &game_id);
+            return;
+        }
+
+        let chess_move = match self.vote_tracker(&game_id).get_top_vote() {
+            Some(Vote::Delay) => {
+                let votes = self.vote_tracker(&game_id);
+                votes.record_decision(CloseReason::Delayed, None);
+                votes.add_delay();
+                votes.reset_voting();
+                votes.schedule_action_vote(game_id);
+                return;
+            }
+            Some(Vote::Resign) => {
+                let played = Vote::Resign.to_string();
+                self.vote_tracker(&game_id)
+                    .record_decision(CloseReason::TimerExpired, played.into());
+                self.finish_team_game(TeamResult::Win(!color));
+                return;
+            }
+            Some(Vote::Draw) if self.teams.can_offer_draw(color) => {
+                let accepted = self.teams.offer_draw(color);
+                let played = Vote::Draw.to_string();
+                let votes = self.vote_tracker(&game_id);
+                votes.record_decision(CloseReason::TimerExpired, played.into());
+
+                if accepted {
+                    self.finish_team_game(TeamResult::Draw);
+                } else {
+                    // The offer stands, but the team still has to pick a move.
+                    votes.reset_voting();
+                    votes.schedule_action_vote(game_id);
+                }
+                return;
+            }
+            Some(Vote::Move(chess_move)) => {
+                let played = chess_move.to_string();
+                self.vote_tracker(&game_id)
+                    .record_decision(CloseReason::TimerExpired, played.into());
+                chess_move
+            }
+            // Draw votes cast before the team's offer was turned down don't count.
+            Some(Vote::Draw) | None => {
+                let random_move = self
+                    .teams
+                    .game()
+                    .and_then(|game| chess::MoveGen::new_legal(&game.board).choose(&mut self.rng));
+                let Some(chess_move) = random_move else {
+                    return;
+                };
+
+                log::info!("Making random move {} for team {}", chess_move.to_string(), &game_id);
+                let played = chess_move.to_string();
+                self.vote_tracker(&game_id).record_decision(CloseReason::NoVotes, played.into());
+                chess_move
+            }
+        };
+
+        let votes = self.vote_tracker(&game_id);
+        votes.reset();
+        votes.disable();
+
+        let is_capture = self
+            .teams
+            .game()
+            .map(|game| game.board.piece_on(chess_move.get_dest()).is_some())
+            .unwrap_or(false);
+        let result = self.teams.play_move(chess_move);
+
+        let clip = if is_capture { Clip::Capture } else { Clip::Move };
+        _ = self.stream_events.send(stream::Event::Action(stream::Action::PlayClip { clip }));
+
+        if let Some(game) = self.teams.game() {
+            let game_update = stream::GameUpdate::Board { board: game.board };
+            let notification = stream::Notification::GameUpdate(game_update);
+            _ = self.stream_events.send(stream::Event::Notification(notification));
+
+            let game_update = stream::GameUpdate::MoveHistory { moves: game.move_history.clone() };
+            let notification = stream::Notification::GameUpdate(game_update);
+            _ = self.stream_events.send(stream::Event::Notification(notification));
+        }
+
+        match result {
+            Some(result) => self.finish_team_game(result),
+            None => self.start_team_turn(),
+        }
+    }
+
+    fn finish_team_game(&mut self, result: TeamResult) {
+        for color in [chess::Color::White, chess::Color::Black] {
+            let game_id = self.teams.game_id(color);
+            self.vote_tracker(&game_id).disable();
+            self.write_vote_audit(&game_id);
+        }
+
+        self.teams.finish_game(result);
+
+        let clip = match result {
+            TeamResult::Win(_) => Clip::Win,
+            TeamResult::Draw => Clip::Draw,
+        };
+        _ = self.stream_events.send(stream::Event::Action(stream::Action::PlayClip { clip }));
+
+        let notification = stream::Notification::State { state: State::GameFinished };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+        self.send_notice(self.teams.notice());
+
+        self.internal_queue.event_sender().send_action(Action::FindNewGame);
+    }
+
+    fn send_team_game(&mut self) {
+        if let Some(game) = self.teams.game_view() {
+            let notification = stream::Notification::ActiveGame { game };
+            _ = self.stream_events.send(stream::Event::Notification(notification));
+        }
+    }
+
+    fn send_head_to_head(&mut self, game_id: Option<&str>) {
+        let record = game_id.and_then(|game_id| self.reputation.head_to_head(game_id));
+        let notification = stream::Notification::HeadToHead { record };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn send_opponent_card(&mut self, game_id: Option<&str>) {
+        let game = game_id.and_then(|game_id| self.game_manager.game(game_id));
+        let card = game.and_then(|game| self.profiles.card(&game.opponent.name));
+        let notification = stream::Notification::OpponentCard { card };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn send_correspondence(&mut self) {
+        let games = self.correspondence.panels();
+        let notification = stream::Notification::Correspondence { games };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn send_rematch(&mut self) {
+        let notification = stream::Notification::Rematch { rematch: self.rematch.view() };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    /// Our rating at the speed of the game on stream, or the one that just finished.
+    fn send_our_rating(&mut self) {
+        let game_mode = match self.game_manager.current_game() {
+            Some(game) => GameMode::from_speed(&game.speed).into(),
+            None => self.game_manager.last_game().map(|game| GameMode::from_speed(&game.speed)),
+        };
+
+        let rating = game_mode.and_then(|game_mode| self.ratings.session(game_mode));
+        let notification = stream::Notification::OurRating { rating };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn send_game_chat(&mut self, game_id: Option<&str>) {
+        let lines = game_id.map(|game_id| self.game_chat.lines(game_id)).unwrap_or_default();
+        let notification = stream::Notification::GameChat { lines };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    /// Lichess TV while there's nothing of our own to show, otherwise clears it.
+    fn send_spectating(&mut self) {
+        let is_idle = self.game_manager.current_game().is_none()
+            && self.game_manager.active_game_count() == 0
+            && !self.teams.is_playing()
+            && !self.rematch.is_open();
+
+        let game = self.tv_game.as_ref().filter(|_| is_idle).map(|game| game.view());
+        let notification = stream::Notification::Spectating { game };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn send_lobby(&mut self) {
+        let notification = stream::Notification::Lobby { lobby: self.lobby.view() };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    fn send_notice(&mut self, notice: Notice) {
+        let notification = stream::Notification::Notice { notice };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    /// The vote pool for a game - its own pool in a simul or team game, otherwise the shared one.
+    fn vote_tracker(&mut self, game_id: &str) -> &mut VoteTracker {
+        if let Some(votes) = self.simul.as_mut().and_then(|simul| simul.votes_mut(game_id)) {
+            return votes;
+        }
+
+        match self.teams.votes_mut(game_id) {
+            Some(votes) => votes,
+            None => &mut self.game_votes,
+        }
+    }
+
     async fn challenge_random_bot(&mut self) {
-        log::info!("Challenging random bot...");
+        if let Some(offer) = self.rematch.take_accepted() {
+            self.challenge_rematch(offer).await;
+            return;
+        }
+
+        // Chat has already picked - work down their list.
+        if let Some(candidate) = self.lobby.next_candidate() {
+            self.challenge_candidate(candidate).await;
+            return;
+        }
+
+        if self.lobby.is_open() {
+            log::info!("Not challenging a bot - chat is still picking one.");
+            return;
+        }
+
+        // Viewers who asked to play go before any bots.
+        if let Some(entry) = self.viewers.pop() {
+            self.send_challenge_queue();
+
+            let time_control = self.enabled_time_controls().choose(&mut self.rng).cloned();
+            let Some(time_control) = time_control else {
+                return;
+            };
+
+            let game_mode = GameMode::from_speed(&time_control.speed);
+            let candidate = Candidate {
+                id: entry.lichess_id,
+                name: entry.lichess_name,
+                rating: entry.ratings.get(&game_mode).copied().unwrap_or_default(),
+                time_control,
+                variant: self.variant(),
+                position: self.starting_position(),
+                origin: ChallengeOrigin::Viewer,
+            };
+            self.challenge_candidate(candidate).await;
+            return;
+        }
+
+        // Human opponents in the lobby, if the account can seek them.
+        if self.create_seek() {
+            return;
+        }
+
+        // Picked first, so bots are chosen on their rating at the speed they'd play.
+        let Some(time_control) = self.enabled_time_controls().choose(&mut self.rng).cloned() else {
+            return;
+        };
+
+        let variant = self.variant();
+
+        log::info!("Challenging random bot at {}...", time_control.label());
 
         let Ok(bots) = self.lichess_actor.get_online_bots().await else {
             self.internal_queue
@@ -379,76 +1153,294 @@ impl Engine {
             return;
         };
 
-        let bots: Vec<User> = bots
-            .into_iter()
-            .filter(|bot| {
-                let tos_violation = bot.tos_violation.unwrap_or(false);
-                let disabled = bot.disabled.unwrap_or(false);
-
-                let valid_blitz = bot
-                    .perfs
-                    .blitz
-                    .as_ref()
-                    .and_then(|blitz| {
-                        Some(blitz.rating != 0 && blitz.prov.unwrap_or(true) && blitz.games > 0)
-                    })
-                    .unwrap_or(false);
-
-                return !tos_violation && !disabled && valid_blitz;
-            })
-            .collect();
+        if self.lobby.is_enabled() {
+            let count = self.lobby.candidate_count();
+            let bots = self.opponents.select_several(
+                &bots,
+                count,
+                &time_control.speed,
+                &variant,
+                &self.reputation,
+                &mut self.difficulty,
+                &mut self.rng,
+            );
+            let candidates: Vec<Candidate> =
+                bots.into_iter().filter_map(|bot| self.candidate(bot, &time_control)).collect();
+
+            if candidates.is_empty() {
+                log::info!("No eligible bots online - retrying");
+                self.internal_queue
+                    .event_sender()
+                    .send_action(Action::Lichess(LichessAction::challenge_random_bot()));
+                return;
+            }
+
+            self.lobby.open(candidates);
+            self.send_lobby();
+
+            let notification = stream::Notification::State { state: State::PickingOpponent };
+            _ = self.stream_events.send(stream::Event::Notification(notification));
+            return;
+        }
 
-        if bots.is_empty() {
+        let selected = self.opponents.select(
+            &bots,
+            &time_control.speed,
+            &variant,
+            &self.reputation,
+            &mut self.difficulty,
+            &mut self.rng,
+        );
+        let Some(candidate) = selected.and_then(|bot| self.candidate(bot, &time_control)) else {
+            log::info!("No eligible bots online - retrying");
             self.internal_queue
                 .event_sender()
                 .send_action(Action::Lichess(LichessAction::challenge_random_bot()));
             return;
+        };
+
+        self.challenge_candidate(candidate).await;
+    }
+
+    /// Seeks a human opponent in the lobby at one of the time controls chat has turned on.
+    /// Returns false if the account can't seek or none of them can be sought, as Board API
+    /// seeks must be rapid or slower and start from the usual position.
+    fn create_seek(&mut self) -> bool {
+        if self.config.lichess.mode != AccountMode::Board || !self.config.seeks.enabled {
+            return false;
         }
 
-        // Turns out to be a decent distribution.
-        let weights = bots
-            .iter()
-            .map(|bot| (500_000.0 / bot.perfs.blitz.as_ref().unwrap().rating as f32) as u64);
-        let distribution = rand::distributions::WeightedIndex::new(weights).unwrap();
-        let bot = &bots[distribution.sample(&mut self.rng)];
+        if self.starting_position().is_some() {
+            return false;
+        }
+
+        let time_controls: Vec<TimeControl> = self
+            .enabled_time_controls()
+            .into_iter()
+            .filter(|time_control| matches!(time_control.speed, Speed::Rapid | Speed::Classical))
+            .collect();
+        let Some(time_control) = time_controls.choose(&mut self.rng).cloned() else {
+            return false;
+        };
+
+        let range = self.config.seeks.rating_range;
+        let rating_range = self
+            .ratings
+            .session(GameMode::from_speed(&time_control.speed))
+            .map(|session| (session.rating.saturating_sub(range), session.rating + range));
+
+        self.cancel_seek();
+        let seek = self.lichess_actor.create_seek(
+            &time_control,
+            self.variant(),
+            rating_range,
+            Duration::from_secs(self.config.seeks.seek_seconds),
+            self.internal_queue.event_sender(),
+        );
+        let seek = match seek {
+            Ok(seek) => seek,
+            Err(error) => {
+                log::warn!("Failed to seek a game: {}", error);
+                return false;
+            }
+        };
+        self.seek = seek.into();
+
+        let state = State::Seeking { time_control: time_control.label() };
+        let notification = stream::Notification::State { state };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+        true
+    }
+
+    /// Withdraws our seek from the lobby, if there is one.
+    fn cancel_seek(&mut self) {
+        if let Some(seek) = self.seek.take() {
+            seek.abort();
+        }
+    }
+
+    /// The bot at the time control it was picked for, shown with the rating it was picked on.
+    fn candidate(&self, bot: &User, time_control: &TimeControl) -> Option<Candidate> {
+        let variant = self.variant();
+        let rating = game_rating(bot, &time_control.speed, &variant)?;
+
+        Candidate {
+            id: bot.id.to_string(),
+            name: bot.username.to_string(),
+            rating,
+            time_control: time_control.clone(),
+            variant,
+            position: self.starting_position(),
+            origin: ChallengeOrigin::Bot,
+        }
+        .into()
+    }
+
+    /// The starting position chat voted for, if they'd rather not start from the usual one.
+    fn starting_position(&self) -> Option<StartingPosition> {
+        let key = self.settings_votes.settings().position?.key;
+        self.positions.get(&key).cloned()
+    }
 
+    /// Chess960 if chat has turned it on, otherwise standard chess. A starting position
+    /// chat voted for takes priority.
+    fn variant(&self) -> VariantKey {
+        if self.starting_position().is_some() {
+            VariantKey::FromPosition
+        } else if self.settings_votes.settings().chess960 {
+            VariantKey::Chess960
+        } else {
+            VariantKey::Standard
+        }
+    }
+
+    /// The time controls whose game modes chat has turned on, or all of them if none are.
+    fn enabled_time_controls(&self) -> Vec<TimeControl> {
         let settings = self.settings_votes.settings();
+        let catalogue = &self.config.time_controls.catalogue;
 
-        let mut rating = bot.perfs.blitz.as_ref().unwrap().rating;
-        let mut clocks = Vec::<(u32, u32)>::default();
+        let enabled: Vec<TimeControl> = catalogue
+            .iter()
+            .filter(|time_control| settings.is_enabled(GameMode::from_speed(&time_control.speed)))
+            .cloned()
+            .collect();
+
+        if enabled.is_empty() {
+            catalogue.clone()
+        } else {
+            enabled
+        }
+    }
+
+    /// Fetches our ratings, e.g. after a game has changed one.
+    async fn refresh_our_rating(&mut self) {
+        let our_id = self.lichess_actor.context.our_id.to_string();
+        let user = match self.lichess_actor.get_user(&our_id).await {
+            Ok(user) => user,
+            Err(error) => {
+                log::warn!("Failed to fetch our ratings: {}", error);
+                return;
+            }
+        };
 
-        if bot.perfs.classical.is_some() && settings.game_modes.classical {
-            rating = bot.perfs.classical.as_ref().unwrap().rating;
-            clocks.push((1800, 0));
+        let speeds = [
+            Speed::UltraBullet,
+            Speed::Bullet,
+            Speed::Blitz,
+            Speed::Rapid,
+            Speed::Classical,
+            Speed::Correspondence,
+        ];
+        for speed in speeds {
+            if let Some(rating) = perf_rating(&user, &speed) {
+                self.ratings.record(GameMode::from_speed(&speed), rating);
+            }
         }
-        if bot.perfs.rapid.is_some() && settings.game_modes.rapid {
-            rating = bot.perfs.rapid.as_ref().unwrap().rating;
-            clocks.push((600, 10));
+
+        self.send_our_rating();
+    }
+
+    /// Caches the opponent's public profile, showing it if their game is on stream.
+    async fn fetch_opponent_profile(&mut self, username: String) {
+        match self.lichess_actor.get_public_profile(&username).await {
+            Ok(profile) => self.profiles.insert(profile),
+            Err(error) => {
+                log::warn!("Failed to fetch {}'s profile: {}", username, error);
+                self.profiles.fetch_failed(&username);
+                return;
+            }
         }
-        if bot.perfs.blitz.is_some() {
-            rating = bot.perfs.blitz.as_ref().unwrap().rating;
-            clocks.push((300, 3));
+
+        let game_id = self.game_manager.current_game().map(|game| game.game_id.clone());
+        self.send_opponent_card(game_id.as_deref());
+    }
+
+    async fn queue_viewer(&mut self, viewer: String, username: String) {
+        if !self.viewers.is_enabled() || self.viewers.contains(&viewer, &username) {
+            return;
         }
-        if bot.perfs.bullet.is_some() && settings.game_modes.bullet {
-            rating = bot.perfs.bullet.as_ref().unwrap().rating;
-            clocks.push((120, 1));
+
+        let user = match self.lichess_actor.get_user(&username).await {
+            Ok(user) => user,
+            Err(error) => {
+                log::info!("Not queueing {} - no Lichess account {}: {}", viewer, username, error);
+                return;
+            }
+        };
+
+        let closed = user.disabled.unwrap_or(false) || user.tos_violation.unwrap_or(false);
+        let is_bot = matches!(user.title, Some(Title::Bot));
+        if closed || is_bot {
+            log::info!("Not queueing {} - {} can't be challenged", viewer, username);
+            return;
         }
 
-        let Some((limit, increment)) = clocks.choose(&mut self.rng) else {
+        let entry = ViewerEntry {
+            viewer,
+            lichess_id: user.id.to_lowercase(),
+            lichess_name: user.username.to_string(),
+            ratings: self
+                .config
+                .time_controls
+                .catalogue
+                .iter()
+                .filter_map(|time_control| {
+                    let rating = perf_rating(&user, &time_control.speed)?;
+                    (GameMode::from_speed(&time_control.speed), rating).into()
+                })
+                .collect(),
+        };
+        if !self.viewers.push(entry) {
             return;
+        }
+
+        self.send_challenge_queue();
+        if self.is_idle() {
+            self.internal_queue.event_sender().send_action(Action::FindNewGame);
+        }
+    }
+
+    /// Whether there's room for another game to start right now.
+    fn is_idle(&self) -> bool {
+        let has_room = match &self.simul {
+            Some(simul) => !simul.is_full(),
+            None => self.game_manager.active_game_count() == 0,
         };
 
-        let user = bot.username.to_string();
-        log::info!("Creating challenge to bot {} ...", &user);
+        // A challenge or seek still open could start a game alongside any other.
+        let is_waiting = self.challenge_manager.outbound().is_some()
+            || self.challenge_manager.is_accepting()
+            || self.seek.as_ref().map_or(false, |seek| !seek.is_finished());
+
+        has_room && !is_waiting && !self.teams.is_playing()
+    }
 
-        let result = self.lichess_actor.create_challenge(user, *limit, *increment).await;
+    /// Everyone waiting to play - accepted challengers first, then viewers.
+    fn send_challenge_queue(&mut self) {
+        let inbound = self.challenge_manager.inbound().iter().map(|c| c.challenger());
+        let viewers = self.viewers.entries().iter().map(|entry| {
+            let rating = entry.ratings.get(&GameMode::Blitz).map(|r| r.to_string());
+            let rating = rating.unwrap_or("????".to_string());
+            format!("{} ({})", entry.lichess_name, rating)
+        });
+
+        let queue = inbound.chain(viewers).collect();
+        let notification = stream::Notification::ChallengeQueue { queue };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+    }
+
+    async fn challenge_candidate(&mut self, candidate: Candidate) {
+        self.rematch.record_challenge(candidate.clone());
+        let Candidate { id, name, rating, time_control, variant, position, origin } = candidate;
+        log::info!("Creating {} challenge to {:?} {} ...", time_control.label(), origin, &name);
+
+        let fen = position.map(|position| position.fen);
+        let result = self.lichess_actor.create_challenge(name, &time_control, variant, fen).await;
         match result {
             Ok(challenge) => {
                 log::info!("Created challenge: id {}", &challenge.challenge.base.id);
-                self.internal_queue.event_sender().send_notification(Notification::ChallengeSent {
-                    id: bot.id.to_string(),
-                    rating,
-                });
+                let notification = Notification::ChallengeSent { id, rating, origin };
+                self.internal_queue.event_sender().send_notification(notification);
             }
             Err(error) => {
                 log::error!("Create challenge error: {} - retrying", error);
@@ -459,14 +1451,49 @@ impl Engine {
         }
     }
 
+    /// Challenges the last opponent again with colours swapped, moving on if that fails.
+    async fn challenge_rematch(&mut self, offer: Offer) {
+        let Offer { candidate, color } = offer;
+        self.rematch.record_challenge(candidate.clone());
+
+        let Candidate { id, name, rating, time_control, variant, position, origin } = candidate;
+        log::info!("Creating {} rematch challenge to {} ...", time_control.label(), &name);
+
+        let fen = position.map(|position| position.fen);
+        let result = self
+            .lichess_actor
+            .create_colored_challenge(name, &time_control, variant, fen, color)
+            .await;
+        match result {
+            Ok(()) => {
+                let notification = Notification::ChallengeSent { id, rating, origin };
+                self.internal_queue.event_sender().send_notification(notification);
+            }
+            Err(error) => {
+                log::error!("Create rematch challenge error: {} - finding another game", error);
+                self.internal_queue.event_sender().send_action(Action::FindNewGame);
+            }
+        }
+    }
+
     async fn make_move(&mut self, game_id: String) {
-        let Some(vote) = self.game_votes.get_top_vote() else {
+        if self.teams.color_for_game_id(&game_id).is_some() {
+            self.make_team_move(game_id);
+            return;
+        }
+
+        if self.correspondence.contains(&game_id) {
+            self.make_correspondence_move(game_id).await;
+            return;
+        }
+
+        let Some(vote) = self.vote_tracker(&game_id).get_top_vote() else {
             let Some(game) = self.game_manager.game(&game_id) else {
                 return;
             };
 
-            let move_generator = chess::MoveGen::new_legal(&game.board);
-            if let Some(chess_move) = move_generator.choose(&mut self.rng) {
+            let legal_moves = game.position.legal_moves();
+            if let Some(chess_move) = legal_moves.choose(&mut self.rng).copied() {
                 log::info!("Making random move {} in game {}", chess_move.to_string(), &game_id);
 
                 let result = self.lichess_actor.make_move(&game_id, chess_move).await;
@@ -474,7 +1501,10 @@ impl Engine {
                     log::error!("Make move error: {}", error.to_string());
                     // reschedule_action_vote(self.internal_queue.event_sender(), &game_id)
                 } else {
-                    self.game_votes.reset();
+                    let played = chess_move.to_string();
+                    let votes = self.vote_tracker(&game_id);
+                    votes.record_decision(CloseReason::NoVotes, played.into());
+                    votes.reset();
                 }
             }
 
@@ -486,9 +1516,10 @@ impl Engine {
 
         match vote {
             self::votes::game::Vote::Delay => {
-                self.game_votes.add_delay();
-                self.game_votes.reset_voting();
-                self.game_votes.schedule_action_vote(game_id);
+                self.vote_tracker(&game_id).record_decision(CloseReason::Delayed, None);
+                self.vote_tracker(&game_id).add_delay();
+                self.vote_tracker(&game_id).reset_voting();
+                self.vote_tracker(&game_id).schedule_action_vote(game_id.to_string());
                 return;
             }
             self::votes::game::Vote::Draw => {
@@ -503,7 +1534,50 @@ impl Engine {
         };
 
         if success {
-            self.game_votes.reset();
+            let played = vote.to_string();
+            self.post_vote_result(&game_id, &played);
+            self.vote_tracker(&game_id).record_decision(CloseReason::TimerExpired, played.into());
+            self.vote_tracker(&game_id).reset();
+        }
+    }
+
+    /// Tells the game chat what Twitch chat voted for, if configured to.
+    fn post_vote_result(&mut self, game_id: &str, played: &str) {
+        if !self.game_chat.posts_votes() {
+            return;
+        }
+
+        let tally = self.vote_tracker(game_id).tally();
+        let votes = tally.get(played).copied().unwrap_or_default();
+        let total: u32 = tally.values().sum();
+
+        let text = format!("Chat voted {} ({}/{} votes)", played, votes, total);
+        let action = LichessAction::write_chat(game_id.to_string(), text);
+        self.internal_queue.event_sender().send_action(Action::Lichess(action));
+    }
+
+    /// Returns the audit, which the game's PGN is annotated from.
+    fn write_vote_audit(&mut self, game_id: &str) -> Option<GameAudit> {
+        let audit = self.vote_tracker(game_id).finish_audit(game_id)?;
+
+        let directory = PathBuf::from(&self.config.storage.directory).join("votes");
+        if let Err(error) = audit.write(&directory) {
+            log::error!("Failed to write vote audit for game {}: {}", game_id, error);
+        }
+
+        audit.into()
+    }
+
+    /// Adds the finished game to the PGN archive.
+    fn archive_game(&mut self, game_id: &str, audit: Option<&GameAudit>) {
+        let last_game = self.game_manager.last_game().filter(|game| game.game_id == game_id);
+        let Some(game) = self.game_manager.game(game_id).or(last_game) else {
+            log::warn!("Failed to archive game {} - it's no longer known", game_id);
+            return;
+        };
+
+        if let Err(error) = self.archive.write(game, audit) {
+            log::error!("Failed to archive game {}: {}", game_id, error);
         }
     }
 
@@ -527,33 +1601,90 @@ impl Engine {
                     self.challenge_manager.process_challenge_declined(challenge)
                 }
                 AccountEvent::GameStart { game } => {
+                    let opponent_id = game.opponent.id.as_ref().unwrap_or(&game.opponent.username);
+                    let origin = self.challenge_manager.game_origin(&game.game_id);
+                    if let Some(origin) = origin {
+                        self.game_origins.insert(game.game_id.to_string(), origin);
+                    }
+                    // Viewers, players who challenged us and those from seeks aren't bots.
+                    if origin == Some(ChallengeOrigin::Bot) {
+                        self.opponents.record_opponent(opponent_id);
+                        self.reputation.record_game_start(game.game_id.to_string(), opponent_id);
+                    }
                     self.game_manager.process_game_start(&game);
                     // Start steraming game events so we get updates.
                     _ = self.external_events.stream_game(&game.game_id).await;
                 }
                 AccountEvent::GameFinish { game } => {
+                    let current_game = self.game_manager.current_game();
+                    let is_displayed =
+                        current_game.map_or(false, |current| current.game_id == game.game_id);
                     self.game_manager.process_game_finish(&game);
+
+                    // The final game state may never come, so the clocks on stream are stopped.
+                    let finished_game = self.game_manager.game(&game.game_id);
+                    if let (true, Some(finished_game)) = (is_displayed, finished_game) {
+                        let timers = [
+                            (Side::Ours, finished_game.us.timer),
+                            (Side::Theirs, finished_game.opponent.timer),
+                        ];
+                        for (side, timer) in timers {
+                            let game_update = stream::GameUpdate::Timer { side, timer };
+                            let notification = stream::Notification::GameUpdate(game_update);
+                            _ = self.stream_events.send(stream::Event::Notification(notification));
+                        }
+                    }
+                    self.game_chat.remove_game(&game.game_id);
+                    self.aborts.remove_game(&game.game_id);
+                    self.focus.remove_game(&game.game_id);
+                    self.teams.record_lichess_game();
+                    if let Some(simul) = &mut self.simul {
+                        _ = simul.remove_game(&game.game_id);
+                        self.send_simul_update();
+                    }
                     // Cleanup finished task.
                     _ = self.external_events.finish_streaming_game(&game.game_id).await;
+                    self.internal_queue
+                        .event_sender()
+                        .send_action(LichessAction::refresh_our_rating().into());
                     self.internal_queue.event_sender().send_action(Action::FindNewGame);
                 }
             },
-            LichessEvent::GameEvent { game_id, event } => {
+            LichessEvent::GameEvent { game_id, event, received_at } => {
                 match event {
                     GameEvent::GameFull { game_full } => {
-                        self.game_manager.process_game_full(&game_full);
+                        self.game_manager.process_game_full(&game_full, received_at);
                     }
                     GameEvent::GameState { game_state } => {
-                        self.game_manager.process_game_update(&game_id, &game_state);
+                        self.game_manager.process_game_update(&game_id, &game_state, received_at);
                     }
                     GameEvent::ChatLine { chat_line } => {
-                        _ = chat_line;
-                        // I don't have any use for these chat lines at the moment.
+                        // Finished games are forgotten, so their chat is too.
+                        if self.game_manager.game(&game_id).is_none() {
+                            return;
+                        }
+
+                        let is_displayed = match self.game_manager.current_game() {
+                            Some(game) => game.game_id == game_id,
+                            None => false,
+                        };
+                        // Lichess names the room, so it's compared by name.
+                        let is_spectator = serde_json::to_value(&chat_line.room)
+                            .map(|room| room == "spectator")
+                            .unwrap_or(false);
+                        if is_spectator && self.config.spectators.enabled {
+                            let username = chat_line.username.to_string();
+                            self.process_spectator_line(&game_id, username, &chat_line.text);
+                        }
+
+                        let recorded =
+                            self.game_chat.record(&game_id, chat_line.username, chat_line.text);
+                        if recorded && is_displayed {
+                            self.send_game_chat(Some(&game_id));
+                        }
                     }
                     GameEvent::OpponentGone { opponent_gone } => {
-                        self.internal_queue.event_sender().send_notification(Notification::Game(
-                            GameNotification::GameAbortable { game_id },
-                        ));
+                        // Whether to abort is left to the first move timer.
                         self.game_manager.process_opponent_gone(&opponent_gone);
                     }
                 }
@@ -584,33 +1715,199 @@ impl Engine {
             TwitchCommand::VoteGame { action } => {
                 self.process_game_vote(user, action);
             }
+            TwitchCommand::VoteCorrespondence { slot, action } => {
+                self.process_correspondence_vote(user, slot, action);
+            }
+            TwitchCommand::VoteBoard { board, action } => {
+                self.process_board_vote(user, board, action);
+            }
+            TwitchCommand::JoinTeam { color } => {
+                self.teams.join(user, color);
+                self.send_team_game();
+            }
+            TwitchCommand::PickOpponent { pick } => {
+                if self.lobby.vote(user, pick) {
+                    self.send_lobby();
+                }
+            }
+            TwitchCommand::VoteRematch { rematch } => {
+                if self.rematch.vote(user, rematch) {
+                    self.send_rematch();
+                }
+            }
+            TwitchCommand::ChallengeMe { username } => {
+                let action = Action::Lichess(LichessAction::queue_viewer(user, username));
+                self.internal_queue.event_sender().send_action(action);
+            }
             TwitchCommand::VoteSetting { setting, on } => {
                 self.process_settings_vote(user, setting, on);
             }
         }
     }
 
-    fn process_game_vote(&mut self, user: String, action: String) {
-        let action = action.to_lowercase();
+    fn process_game_vote(&mut self, user: String, text: String) {
+        if self.teams.is_playing() {
+            self.process_team_vote(user, text);
+            return;
+        }
 
-        let vote = if action == "delay" {
+        let game_id = self.game_manager.current_game().map(|game| game.game_id.to_string());
+        let vote = self.parse_vote(game_id.as_deref(), &text);
+
+        let votes = match &game_id {
+            Some(game_id) => self.vote_tracker(game_id),
+            None => &mut self.game_votes,
+        };
+
+        if let Some(vote) = vote {
+            votes.add_vote(user, text, vote);
+        } else {
+            votes.reject_vote(user, text);
+        }
+    }
+
+    fn process_board_vote(&mut self, user: String, board: char, text: String) {
+        let Some(game_id) = self.simul.as_ref().and_then(|simul| simul.game_id(board)) else {
+            log::warn!("No game on board {} for vote from {}", board, &user);
+            return;
+        };
+
+        let vote = self.parse_vote(Some(&game_id), &text);
+        let votes = self.vote_tracker(&game_id);
+
+        if let Some(vote) = vote {
+            votes.add_vote(user, text, vote);
+        } else {
+            votes.reject_vote(user, text);
+        }
+    }
+
+    fn process_correspondence_vote(&mut self, user: String, slot: usize, text: String) {
+        let Some(game_id) = self.correspondence.game_id(slot).cloned() else {
+            log::warn!("No correspondence game {} for vote from {}", slot, &user);
+            return;
+        };
+
+        let Some(chess_move) = self.game_manager.convert_move_in_game(&game_id, text) else {
+            return;
+        };
+
+        if self.correspondence.add_vote(&game_id, user, chess_move) {
+            log::info!("Vote quota reached in correspondence game {}", &game_id);
+            let action = Action::Lichess(LichessAction::make_move(game_id));
+            self.internal_queue.event_sender().send_action(action);
+        }
+        self.send_correspondence();
+    }
+
+    /// Spectators vote in the Lichess game chat with the same `!game` command as Twitch chat.
+    fn process_spectator_line(&mut self, game_id: &str, username: String, text: &str) {
+        let Ok(TwitchCommand::VoteGame { action }) = TwitchCommand::from_str(text) else {
+            return;
+        };
+
+        // Outside a simul the shared pool belongs to the game on stream, so only its
+        // spectators can vote.
+        let has_own_pool = self.simul.as_mut().and_then(|simul| simul.votes_mut(game_id)).is_some();
+        let is_displayed = match self.game_manager.current_game() {
+            Some(game) => game.game_id == game_id,
+            None => false,
+        };
+        if !has_own_pool && !is_displayed {
+            return;
+        }
+
+        // Kept apart from Twitch users who happen to have the same name.
+        let user = format!("lichess:{}", username.to_lowercase());
+        let command = Command::new(user.to_string(), action.to_string());
+        let notification = stream::Notification::ChatCommand { command };
+        _ = self.stream_events.send(stream::Event::Notification(notification));
+
+        let vote = self.parse_vote(Some(game_id), &action);
+        let votes = self.vote_tracker(game_id);
+
+        if let Some(vote) = vote {
+            votes.add_spectator_vote(user, action, vote);
+        } else {
+            votes.reject_vote(user, action);
+        }
+    }
+
+    fn process_team_vote(&mut self, user: String, text: String) {
+        let Some(color) = self.teams.team_of(&user) else {
+            log::info!("Ignoring vote from {} - they haven't joined a team", &user);
+            return;
+        };
+
+        let game_id = self.teams.game_id(color);
+        let vote = self.parse_vote(Some(&game_id), &text);
+        // Once the team has offered a draw, more draw votes don't re-open the offer.
+        let vote = vote.filter(|vote| vote != &Vote::Draw || self.teams.can_offer_draw(color));
+        let votes = self.vote_tracker(&game_id);
+
+        if let Some(vote) = vote {
+            votes.add_vote(user, text, vote);
+        } else {
+            votes.reject_vote(user, text);
+        }
+    }
+
+    fn parse_vote(&self, game_id: Option<&str>, text: &str) -> Option<Vote> {
+        let action = text.to_lowercase();
+
+        if action == "delay" {
             self::votes::game::Vote::Delay.into()
         } else if action == "draw" {
             self::votes::game::Vote::Draw.into()
         } else if action == "resign" {
             self::votes::game::Vote::Resign.into()
-        } else if let Some(chess_move) = self.game_manager.convert_move(action) {
+        } else if let Some(game_id) = game_id {
+            let chess_move = if self.teams.color_for_game_id(game_id).is_some() {
+                self.teams.convert_move(action)?
+            } else {
+                self.game_manager.convert_move_in_game(game_id, action)?
+            };
             self::votes::game::Vote::Move(chess_move).into()
         } else {
             None
-        };
-
-        if let Some(vote) = vote {
-            self.game_votes.add_vote(user, vote);
         }
     }
 
     fn process_settings_vote(&mut self, user: String, setting: Setting, on: bool) {
+        if let Setting::StartingPosition(key) = &setting {
+            if on && !self.positions.contains(key) {
+                log::info!("Ignoring vote from {} - no starting position {}", &user, key);
+                return;
+            }
+        }
+
         self.settings_votes.add_vote(user, setting, on);
     }
 }
+
+/// The rating a bot is shown with, and picked on, for a game at this speed and variant.
+/// Chess960 has one rating whatever the speed, and bots without one likely don't play it.
+fn game_rating(user: &User, speed: &Speed, variant: &VariantKey) -> Option<u32> {
+    let rating = perf_rating(user, speed)?;
+    match variant {
+        VariantKey::Chess960 => {
+            let chess960 = user.perfs.chess960.as_ref().filter(|perf| perf.games > 0);
+            chess960.map(|perf| perf.rating)
+        }
+        _ => rating.into(),
+    }
+}
+
+/// The user's rating at a speed, if they've played any games at it.
+fn perf_rating(user: &User, speed: &Speed) -> Option<u32> {
+    let perf = match speed {
+        Speed::UltraBullet => &user.perfs.ultra_bullet,
+        Speed::Bullet => &user.perfs.bullet,
+        Speed::Blitz => &user.perfs.blitz,
+        Speed::Rapid => &user.perfs.rapid,
+        Speed::Classical => &user.perfs.classical,
+        Speed::Correspondence => &user.perfs.correspondence,
+    };
+
+    perf.as_ref().filter(|perf| perf.games > 0).map(|perf| perf.rating)
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashSet, VecDeque};
+
+use lichess_api::model::users::User;
+use lichess_api::model::{Speed, VariantKey};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::config::{self, OpponentPolicy};
+use crate::engine::difficulty::{DifficultyController, RatingPool};
+use crate::engine::game_rating;
+use crate::engine::reputation::Reputation;
+
+/// Picks opponents according to the configured policy and allow/block lists.
+pub struct OpponentSelector {
+    policy: OpponentPolicy,
+    roster: Vec<String>,
+    roster_index: usize,
+    allowlist: HashSet<String>,
+    blocklist: HashSet<String>,
+    avoid_repeats: usize,
+    recent: VecDeque<String>,
+}
+
+impl OpponentSelector {
+    pub fn new(config: &config::Opponents) -> Self {
+        let ids = |ids: &Vec<String>| ids.iter().map(|id| id.to_lowercase()).collect();
+
+        Self {
+            policy: config.policy,
+            roster: ids(&config.roster),
+            roster_index: 0,
+            allowlist: ids(&config.allowlist),
+            blocklist: ids(&config.blocklist),
+            avoid_repeats: config.avoid_repeats,
+            recent: Default::default(),
+        }
+    }
+
+    /// Remembers an opponent so they aren't picked again within the next few games.
+    pub fn record_opponent(&mut self, id: &str) {
+        if self.avoid_repeats == 0 {
+            return;
+        }
+
+        self.recent.push_back(id.to_lowercase());
+        while self.recent.len() > self.avoid_repeats {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Whether a bot can be challenged to a game at this speed and variant, which it must have
+    /// a rating for.
+    pub fn is_eligible(&self, bot: &User, speed: &Speed, variant: &VariantKey) -> bool {
+        let tos_violation = bot.tos_violation.unwrap_or(false);
+        let disabled = bot.disabled.unwrap_or(false);
+        let rated = game_rating(bot, speed, variant).map_or(false, |rating| rating != 0);
+
+        let id = bot.id.to_lowercase();
+        let allowed = self.allowlist.is_empty() || self.allowlist.contains(&id);
+        let blocked = self.blocklist.contains(&id);
+        let recent = self.recent.contains(&id);
+
+        !tos_violation && !disabled && rated && allowed && !blocked && !recent
+    }
+
+    /// Picks a bot to challenge at this speed and variant, or None if nobody online is eligible.
+    /// Bots that tend to decline or abort are picked less often.
+    pub fn select<'a>(
+        &mut self,
+        bots: &'a [User],
+        speed: &Speed,
+        variant: &VariantKey,
+        reputation: &Reputation,
+        difficulty: &mut DifficultyController,
+        rng: &mut impl Rng,
+    ) -> Option<&'a User> {
+        let bots = bots.iter().filter(|bot| self.is_eligible(bot, speed, variant)).collect();
+        self.pick(bots, speed, variant, reputation, difficulty, rng)
+    }
+
+    /// Picks up to `count` different bots, e.g. for chat to choose between.
+    pub fn select_several<'a>(
+        &mut self,
+        bots: &'a [User],
+        count: usize,
+        speed: &Speed,
+        variant: &VariantKey,
+        reputation: &Reputation,
+        difficulty: &mut DifficultyController,
+        rng: &mut impl Rng,
+    ) -> Vec<&'a User> {
+        let mut picked = Vec::<&User>::with_capacity(count);
+
+        while picked.len() < count {
+            let remaining = bots
+                .iter()
+                .filter(|bot| self.is_eligible(bot, speed, variant))
+                .filter(|bot| !picked.iter().any(|picked| picked.id == bot.id))
+                .collect();
+
+            let picked_bot = self.pick(remaining, speed, variant, reputation, difficulty, rng);
+            let Some(bot) = picked_bot else {
+                break;
+            };
+            picked.push(bot);
+        }
+
+        picked
+    }
+
+    /// Bots are compared by the rating they're shown with - the one for the game they'd play.
+    fn pick<'a>(
+        &mut self,
+        bots: Vec<&'a User>,
+        speed: &Speed,
+        variant: &VariantKey,
+        reputation: &Reputation,
+        difficulty: &mut DifficultyController,
+        rng: &mut impl Rng,
+    ) -> Option<&'a User> {
+        let (bots, ratings): (Vec<&'a User>, Vec<u32>) = bots
+            .into_iter()
+            .filter_map(|bot| {
+                let rating = game_rating(bot, speed, variant).filter(|rating| *rating != 0)?;
+                (bot, rating).into()
+            })
+            .unzip();
+        if bots.is_empty() {
+            return None;
+        }
+
+        let reliabilities: Vec<f32> =
+            bots.iter().map(|bot| reputation.reliability(&bot.id)).collect();
+
+        let index = match self.policy {
+            OpponentPolicy::WeightedRandom => weighted_random(&ratings, &reliabilities, rng),
+            OpponentPolicy::RatingMatched => {
+                let pool = RatingPool::new(speed, variant);
+                rating_matched(&ratings, &reliabilities, pool, difficulty, rng)
+            }
+            OpponentPolicy::RoundRobin => match self.next_in_roster(&bots) {
+                Some(index) => index,
+                None => {
+                    log::info!("[OpponentSelector] Nobody in the roster is online");
+                    weighted_random(&ratings, &reliabilities, rng)
+                }
+            },
+        };
+
+        bots.get(index).copied()
+    }
+
+    fn next_in_roster(&mut self, bots: &[&User]) -> Option<usize> {
+        for offset in 0..self.roster.len() {
+            let roster_index = (self.roster_index + offset) % self.roster.len();
+            let id = &self.roster[roster_index];
+
+            if let Some(index) = bots.iter().position(|bot| bot.id.to_lowercase() == *id) {
+                self.roster_index = roster_index + 1;
+                return index.into();
+            }
+        }
+
+        None
+    }
+}
+
+fn weighted_random(ratings: &[u32], reliabilities: &[f32], rng: &mut impl Rng) -> usize {
+    // Turns out to be a decent distribution.
+    let weights = ratings.iter().map(|rating| 500_000.0 / *rating as f32);
+    let weights = weights.zip(reliabilities).map(|(weight, reliability)| weight * reliability);
+    WeightedIndex::new(weights).unwrap().sample(rng)
+}
+
+fn rating_matched(
+    ratings: &[u32],
+    reliabilities: &[f32],
+    pool: RatingPool,
+    difficulty: &mut DifficultyController,
+    rng: &mut impl Rng,
+) -> usize {
+    loop {
+        let weights = ratings.iter().map(|rating| difficulty.weight(pool, *rating) as f32);
+        let weights = weights.zip(reliabilities).map(|(weight, reliability)| weight * reliability);
+        if let Ok(distribution) = WeightedIndex::new(weights) {
+            return distribution.sample(rng);
+        }
+
+        if !difficulty.widen() {
+            log::info!("[OpponentSelector] No bots within the widest difficulty band");
+            return rng.gen_range(0..ratings.len());
+        }
+    }
+}
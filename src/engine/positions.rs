@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lichess::position::Position;
+
+/// Voting for this plays from the usual starting position.
+pub const STANDARD: &str = "standard";
+
+/// Named starting positions chat can vote to play from.
+pub struct PositionLibrary {
+    positions: Vec<StartingPosition>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct StartingPosition {
+    /// What chat types to vote for it, e.g. `!position lucena`.
+    pub key: String,
+    pub name: String,
+    pub category: Category,
+    pub fen: String,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Middlegame,
+    Endgame,
+    /// One side starts without some material.
+    Odds,
+}
+
+impl PositionLibrary {
+    /// Loads positions from the path, skipping any that don't parse.
+    pub fn load(path: &Path) -> Self {
+        let positions = File::open(path)
+            .map_err(crate::error::Error::from)
+            .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?));
+
+        let positions: Vec<StartingPosition> = match positions {
+            Ok(positions) => positions,
+            Err(error) => {
+                log::info!("[Positions] No positions loaded from {:?}: {}", path, error);
+                Default::default()
+            }
+        };
+
+        let positions = positions
+            .into_iter()
+            .filter(|position| {
+                let is_valid = Position::from_fen(&position.fen, false).is_some();
+                if !is_valid {
+                    log::warn!("[Positions] Skipping {} - invalid FEN", &position.key);
+                }
+                is_valid && position.key != STANDARD
+            })
+            .collect();
+
+        Self { positions }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&StartingPosition> {
+        self.positions.iter().find(|position| position.key == key)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        key == STANDARD || self.get(key).is_some()
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::lichess::profile::PublicProfile;
+use crate::stream::model::OpponentCard;
+
+/// Perfs in the order they're listed on Lichess, named as shown on the opponent card.
+const PERFS: [(&str, &str); 7] = [
+    ("ultraBullet", "Ultra"),
+    ("bullet", "Bullet"),
+    ("blitz", "Blitz"),
+    ("rapid", "Rapid"),
+    ("classical", "Classical"),
+    ("correspondence", "Daily"),
+    ("chess960", "960"),
+];
+
+/// Opponents' public profiles, fetched once each per run.
+#[derive(Default)]
+pub struct OpponentProfiles {
+    profiles: HashMap<String, PublicProfile>,
+    requested: HashSet<String>,
+}
+
+impl OpponentProfiles {
+    /// True the first time it's asked about an opponent whose profile isn't cached.
+    pub fn should_fetch(&mut self, opponent: &str) -> bool {
+        let opponent = opponent.to_lowercase();
+        !self.profiles.contains_key(&opponent) && self.requested.insert(opponent)
+    }
+
+    /// Lets a failed fetch be retried next game.
+    pub fn fetch_failed(&mut self, opponent: &str) {
+        self.requested.remove(&opponent.to_lowercase());
+    }
+
+    pub fn insert(&mut self, profile: PublicProfile) {
+        self.profiles.insert(profile.id.to_lowercase(), profile);
+    }
+
+    pub fn card(&self, opponent: &str) -> Option<OpponentCard> {
+        let profile = self.profiles.get(&opponent.to_lowercase())?;
+
+        let mut ratings: Vec<(u32, &'static str, u32)> = PERFS
+            .iter()
+            .filter_map(|(key, name)| {
+                let perf = profile.perfs.get(*key).filter(|perf| perf.games > 0)?;
+                (perf.games, *name, perf.rating?).into()
+            })
+            .collect();
+        ratings.sort_by(|l, r| r.0.cmp(&l.0));
+
+        OpponentCard {
+            title: profile.title.clone(),
+            ratings: ratings.into_iter().map(|(_, name, rating)| (name, rating)).collect(),
+            bio: profile.bio_line(),
+            created_at: profile.created_at(),
+            games_played: profile.count.as_ref().map(|count| count.all),
+        }
+        .into()
+    }
+}
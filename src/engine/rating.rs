@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+
+use crate::stream::model::SessionRating;
+use crate::twitch::command::GameMode;
+
+/// Our own rating at each speed, and how it's moved since the stream started.
+#[derive(Default)]
+pub struct RatingTracker {
+    /// Each rating seen at a speed this session, oldest first.
+    history: HashMap<GameMode, Vec<u32>>,
+}
+
+impl RatingTracker {
+    pub fn record(&mut self, game_mode: GameMode, rating: u32) {
+        let history = self.history.entry(game_mode).or_default();
+        if history.last() != Some(&rating) {
+            history.push(rating);
+        }
+    }
+
+    pub fn session(&self, game_mode: GameMode) -> Option<SessionRating> {
+        let history = self.history.get(&game_mode)?;
+        let (first, last) = (*history.first()?, *history.last()?);
+
+        SessionRating { rating: last, change: last as i32 - first as i32, history: history.clone() }
+            .into()
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::config;
+use crate::engine::events::internal::{EventSender, Notification};
+use crate::engine::lobby::Candidate;
+use crate::engine::votes::Username;
+use crate::lichess::game::{Game, GameOutcome};
+use crate::stream::model::{RematchView, Series};
+
+/// After a game, chat votes whether to play the same opponent again with colours swapped.
+pub struct Rematch {
+    enabled: bool,
+    vote_duration: Duration,
+    /// The last challenge we sent, so the game it led to can be offered again.
+    last_challenge: Option<Candidate>,
+    offer: Option<Offer>,
+    /// The rematch chat voted for, waiting to be challenged.
+    accepted: Option<Offer>,
+    votes: HashMap<Username, bool>,
+    series: Option<(String, Series)>,
+    timer: Option<RematchTimer>,
+    event_sender: EventSender,
+}
+
+struct RematchTimer {
+    start: Instant,
+    timer_handle: JoinHandle<()>,
+}
+
+/// The same opponent, clock and starting position as the last game.
+#[derive(Clone)]
+pub struct Offer {
+    pub candidate: Candidate,
+    /// The colour chat plays this time.
+    pub color: chess::Color,
+}
+
+impl Rematch {
+    pub fn new(config: &config::Rematch, event_sender: EventSender) -> Self {
+        Self {
+            enabled: config.enabled,
+            vote_duration: Duration::from_secs(config.vote_seconds),
+            last_challenge: None,
+            offer: None,
+            accepted: None,
+            votes: Default::default(),
+            series: None,
+            timer: None,
+            event_sender,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.offer.is_some()
+    }
+
+    pub fn record_challenge(&mut self, candidate: Candidate) {
+        self.last_challenge = candidate.into();
+    }
+
+    /// Adds to the series against the opponent, or starts a new one against someone else.
+    pub fn record_result(&mut self, opponent_id: &str, outcome: GameOutcome) {
+        let opponent_id = opponent_id.to_lowercase();
+        let is_same_opponent = self.series.as_ref().map(|(id, _)| *id == opponent_id);
+        if is_same_opponent != Some(true) {
+            self.series = (opponent_id, Series::default()).into();
+        }
+
+        let Some((_, series)) = &mut self.series else {
+            return;
+        };
+
+        match outcome {
+            GameOutcome::Win => series.wins += 1,
+            GameOutcome::Loss => series.losses += 1,
+            GameOutcome::Draw => series.draws += 1,
+        }
+    }
+
+    /// Opens voting if the game was against the last opponent we challenged. Games from
+    /// challenges we accepted aren't offered, as we don't know the exact clock they used.
+    pub fn open(&mut self, game: &Game) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let opponent_id = game.opponent.name.to_lowercase();
+        let Some(candidate) = self.last_challenge.clone() else {
+            return false;
+        };
+
+        if candidate.id != opponent_id {
+            return false;
+        }
+
+        self.clear();
+        self.offer = Offer { candidate, color: !game.us.color }.into();
+
+        let mut event_sender = self.event_sender.clone();
+        let vote_duration = self.vote_duration;
+
+        let timer_handle = tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            for _ in 0..vote_duration.as_secs() {
+                interval.tick().await;
+                event_sender.send_notification(Notification::RematchTick);
+            }
+            event_sender.send_notification(Notification::RematchClosed);
+        });
+
+        self.timer = RematchTimer { start: Instant::now(), timer_handle }.into();
+        log::info!("[Rematch] Voting on a rematch against {}", &opponent_id);
+        true
+    }
+
+    /// Returns false if there's nothing to vote on.
+    pub fn vote(&mut self, user: Username, rematch: bool) -> bool {
+        if !self.is_open() {
+            return false;
+        }
+
+        self.votes.insert(user, rematch);
+        true
+    }
+
+    /// Closes voting. Returns true if more of chat wanted the rematch than didn't.
+    pub fn close(&mut self) -> bool {
+        let (yes, no) = self.tally();
+        let offer = self.offer.take();
+        self.clear();
+
+        self.accepted = offer.filter(|_| yes > no);
+        match &self.accepted {
+            Some(offer) => {
+                log::info!("[Rematch] Chat wants to play {} again", &offer.candidate.name)
+            }
+            None => log::info!("[Rematch] Chat is moving on ({} yes, {} no)", yes, no),
+        }
+
+        self.accepted.is_some()
+    }
+
+    pub fn take_accepted(&mut self) -> Option<Offer> {
+        self.accepted.take()
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(timer) = &self.timer {
+            timer.timer_handle.abort();
+        }
+
+        self.offer = None;
+        self.votes.clear();
+        self.timer = None;
+    }
+
+    pub fn view(&self) -> Option<RematchView> {
+        let offer = self.offer.as_ref()?;
+
+        let seconds_remaining = match &self.timer {
+            Some(timer) => self.vote_duration.saturating_sub(timer.start.elapsed()).as_secs(),
+            None => 0,
+        };
+
+        let (yes, no) = self.tally();
+        let series = self.series.as_ref().map(|(_, series)| *series).unwrap_or_default();
+
+        RematchView {
+            opponent: offer.candidate.name.to_string(),
+            seconds_remaining,
+            yes,
+            no,
+            series,
+        }
+        .into()
+    }
+
+    fn tally(&self) -> (u32, u32) {
+        let yes = self.votes.values().filter(|rematch| **rematch).count() as u32;
+        (yes, self.votes.len() as u32 - yes)
+    }
+}
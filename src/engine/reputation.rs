@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::lichess::challenge::ChallengeFailure;
+use crate::lichess::game::{GameId, GameOutcome};
+use crate::stream::model::HeadToHead;
+
+/// How much a bot that never answers is still weighted, relative to a reliable one.
+const MIN_RELIABILITY: f32 = 0.1;
+
+/// Per-opponent history of challenges and games, persisted across restarts.
+pub struct Reputation {
+    path: PathBuf,
+    records: HashMap<String, OpponentRecord>,
+    games: HashMap<GameId, String>,
+    pending: Option<String>,
+}
+
+/// Games are counted from chat's side, so `won` is a game chat won.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct OpponentRecord {
+    pub challenges: u32,
+    pub declined: u32,
+    pub timed_out: u32,
+    pub aborted: u32,
+    /// Games aborted because they never made their first move, also counted in `aborted`.
+    #[serde(default)]
+    pub no_shows: u32,
+    pub won: u32,
+    pub lost: u32,
+    pub drawn: u32,
+}
+
+impl Reputation {
+    /// Loads records from the path, starting afresh if there aren't any yet.
+    pub fn load(path: PathBuf) -> Self {
+        let records = File::open(&path)
+            .map_err(crate::error::Error::from)
+            .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?));
+
+        let records = match records {
+            Ok(records) => records,
+            Err(error) => {
+                log::info!("[Reputation] No records loaded from {:?}: {}", &path, error);
+                Default::default()
+            }
+        };
+
+        Self { path, records, games: Default::default(), pending: None }
+    }
+
+    pub fn record(&self, opponent_id: &str) -> Option<&OpponentRecord> {
+        self.records.get(&opponent_id.to_lowercase())
+    }
+
+    pub fn opponent(&self, game_id: &str) -> Option<&String> {
+        self.games.get(game_id)
+    }
+
+    pub fn head_to_head(&self, game_id: &str) -> Option<HeadToHead> {
+        let record = self.record(self.opponent(game_id)?)?;
+        HeadToHead { wins: record.won, losses: record.lost, draws: record.drawn }.into()
+    }
+
+    /// Between MIN_RELIABILITY and 1, lower for bots that tend to decline, time out or abort.
+    pub fn reliability(&self, opponent_id: &str) -> f32 {
+        let Some(record) = self.record(opponent_id) else {
+            return 1.0;
+        };
+
+        let failures = (record.declined + record.timed_out + record.aborted) as f32;
+        // Smoothed so that a single decline doesn't write a bot off.
+        let failure_rate = failures / (record.challenges as f32 + 2.0);
+
+        (1.0 - failure_rate).clamp(MIN_RELIABILITY, 1.0)
+    }
+
+    pub fn record_challenge(&mut self, opponent_id: &str) {
+        let opponent_id = opponent_id.to_lowercase();
+        self.record_mut(&opponent_id).challenges += 1;
+        self.pending = opponent_id.into();
+        self.save();
+    }
+
+    pub fn record_unanswered(&mut self, failure: ChallengeFailure) {
+        let Some(opponent_id) = self.pending.take() else {
+            return;
+        };
+
+        let record = self.record_mut(&opponent_id);
+        match failure {
+            ChallengeFailure::Declined => record.declined += 1,
+            ChallengeFailure::TimedOut => record.timed_out += 1,
+        }
+        self.save();
+    }
+
+    pub fn record_game_start(&mut self, game_id: GameId, opponent_id: &str) {
+        self.pending = None;
+        self.games.insert(game_id, opponent_id.to_lowercase());
+    }
+
+    /// Records how a game ended - None if it was aborted.
+    pub fn record_outcome(&mut self, game_id: &str, outcome: Option<GameOutcome>) {
+        let Some(opponent_id) = self.games.get(game_id).cloned() else {
+            return;
+        };
+
+        let record = self.record_mut(&opponent_id);
+        match outcome {
+            Some(GameOutcome::Win) => record.won += 1,
+            Some(GameOutcome::Loss) => record.lost += 1,
+            Some(GameOutcome::Draw) => record.drawn += 1,
+            None => record.aborted += 1,
+        }
+        self.save();
+    }
+
+    /// Records that we aborted a game because the opponent never made their first move.
+    pub fn record_no_show(&mut self, game_id: &str) {
+        let Some(opponent_id) = self.games.get(game_id).cloned() else {
+            return;
+        };
+
+        self.record_mut(&opponent_id).no_shows += 1;
+        self.save();
+    }
+
+    fn record_mut(&mut self, opponent_id: &str) -> &mut OpponentRecord {
+        self.records.entry(opponent_id.to_string()).or_default()
+    }
+
+    fn save(&self) {
+        if let Err(error) = self.write() {
+            log::error!("[Reputation] Failed to save records to {:?}: {}", &self.path, error);
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        let writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(writer, &self.records)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use lichess_api::model::Speed;
+
+use crate::engine::events::internal::EventSender;
+use crate::engine::votes::game::VoteTracker;
+use crate::lichess::game::GameId;
+
+/// Chat addresses each board with its label, e.g. `!a e2e4`.
+pub const LABELS: [char; 4] = ['a', 'b', 'c', 'd'];
+
+/// Several games at once, each with its own vote pool and timer.
+pub struct Simul {
+    boards: Vec<SimulBoard>,
+    max_boards: usize,
+    event_sender: EventSender,
+}
+
+pub struct SimulBoard {
+    pub label: char,
+    pub game_id: GameId,
+    pub votes: VoteTracker,
+}
+
+impl Simul {
+    pub fn new(max_boards: usize, event_sender: EventSender) -> Self {
+        let max_boards = max_boards.clamp(1, LABELS.len());
+        Self { boards: Default::default(), max_boards, event_sender }
+    }
+
+    pub fn boards(&self) -> &Vec<SimulBoard> {
+        &self.boards
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.boards.len() >= self.max_boards
+    }
+
+    pub fn contains(&self, game_id: &str) -> bool {
+        self.boards.iter().any(|board| board.game_id == game_id)
+    }
+
+    /// Gives the game a free board, returning its label.
+    pub fn add_game(&mut self, game_id: GameId, speed: &Speed) -> Option<char> {
+        if let Some(board) = self.boards.iter().find(|board| board.game_id == game_id) {
+            return board.label.into();
+        }
+
+        if self.is_full() {
+            log::warn!("[Simul] No free board for game {}", &game_id);
+            return None;
+        }
+
+        let label = LABELS
+            .iter()
+            .find(|label| !self.boards.iter().any(|board| board.label == **label))
+            .copied()?;
+
+        let mut votes = VoteTracker::new(speed, self.event_sender.clone());
+        votes.set_game(game_id.to_string());
+
+        log::info!("[Simul] Game {} is on board {}", &game_id, label);
+
+        self.boards.push(SimulBoard { label, game_id, votes });
+        self.boards.sort_by_key(|board| board.label);
+
+        label.into()
+    }
+
+    pub fn remove_game(&mut self, game_id: &str) -> Option<SimulBoard> {
+        let index = self.boards.iter().position(|board| board.game_id == game_id)?;
+        self.boards.remove(index).into()
+    }
+
+    pub fn game_id(&self, label: char) -> Option<GameId> {
+        self.boards.iter().find(|board| board.label == label).map(|board| board.game_id.to_string())
+    }
+
+    pub fn votes_mut(&mut self, game_id: &str) -> Option<&mut VoteTracker> {
+        self.boards.iter_mut().find(|board| board.game_id == game_id).map(|board| &mut board.votes)
+    }
+}
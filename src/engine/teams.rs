@@ -0,0 +1,327 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::{Instant, SystemTime};
+
+use chess::{BitBoard, BoardStatus, ChessMove, Piece};
+use lichess_api::model::{Speed, VariantKey};
+
+use crate::engine::events::internal::EventSender;
+use crate::engine::votes::game::VoteTracker;
+use crate::engine::votes::Username;
+use crate::lichess::game::{Game, GameId};
+use crate::lichess::position::Position;
+use crate::stream::model::{Notice, Player, Timer};
+
+/// The fifty-move rule, counted in plies.
+const FIFTY_MOVE_PLIES: u32 = 100;
+/// The squares b1, d1 and so on.
+const LIGHT_SQUARES: u64 = 0x55AA_55AA_55AA_55AA;
+
+/// Chat against chat on a local board, with each half of chat voting for its own side.
+pub struct TeamMode {
+    enabled: bool,
+    lichess_games_between: u32,
+    lichess_games: u32,
+    game_number: u32,
+    white: Team,
+    black: Team,
+    draws: u32,
+    game: Option<TeamGame>,
+    event_sender: EventSender,
+}
+
+pub struct Team {
+    pub color: chess::Color,
+    pub members: HashSet<Username>,
+    pub wins: u32,
+    pub votes: VoteTracker,
+}
+
+pub struct TeamGame {
+    pub board: chess::Board,
+    pub move_history: Vec<String>,
+    pub last_move: Option<ChessMove>,
+    pub draw_offer: Option<chess::Color>,
+    /// Teams whose draw offer was turned down, who can't offer again this game.
+    pub draws_declined: HashSet<chess::Color>,
+    /// How often each position has come up, by its hash.
+    pub repetitions: HashMap<u64, u32>,
+    /// Plies since the last capture or pawn move.
+    pub halfmove_clock: u32,
+    pub started: Instant,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TeamResult {
+    Win(chess::Color),
+    Draw,
+}
+
+impl TeamMode {
+    pub fn new(enabled: bool, lichess_games_between: u32, event_sender: EventSender) -> Self {
+        Self {
+            enabled,
+            lichess_games_between,
+            lichess_games: 0,
+            game_number: 0,
+            white: Team::new(chess::Color::White, event_sender.clone()),
+            black: Team::new(chess::Color::Black, event_sender.clone()),
+            draws: 0,
+            game: None,
+            event_sender,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.game.is_some()
+    }
+
+    /// Whether enough Lichess games have been played since the last team game.
+    pub fn is_due(&self) -> bool {
+        self.enabled && !self.is_playing() && self.lichess_games >= self.lichess_games_between
+    }
+
+    pub fn record_lichess_game(&mut self) {
+        self.lichess_games += 1;
+    }
+
+    pub fn start_game(&mut self) {
+        self.game_number += 1;
+        self.lichess_games = 0;
+
+        for color in [chess::Color::White, chess::Color::Black] {
+            let mut votes = VoteTracker::new(&Speed::Blitz, self.event_sender.clone());
+            votes.set_game(self.game_id(color));
+            self.team_mut(color).votes = votes;
+        }
+
+        self.game = TeamGame {
+            board: chess::Board::default(),
+            move_history: Default::default(),
+            last_move: None,
+            draw_offer: None,
+            draws_declined: Default::default(),
+            repetitions: Default::default(),
+            halfmove_clock: 0,
+            started: Instant::now(),
+        }
+        .into();
+
+        log::info!("[TeamMode] Team game {} started", self.game_number);
+    }
+
+    pub fn finish_game(&mut self, result: TeamResult) {
+        match result {
+            TeamResult::Win(color) => self.team_mut(color).wins += 1,
+            TeamResult::Draw => self.draws += 1,
+        }
+
+        log::info!("[TeamMode] Team game {} finished: {:?}", self.game_number, result);
+        self.game = None;
+    }
+
+    pub fn join(&mut self, user: Username, color: chess::Color) {
+        self.team_mut(!color).members.remove(&user);
+        self.team_mut(color).members.insert(user);
+    }
+
+    pub fn team_of(&self, user: &Username) -> Option<chess::Color> {
+        [&self.white, &self.black]
+            .into_iter()
+            .find(|team| team.members.contains(user))
+            .map(|team| team.color)
+    }
+
+    pub fn game(&self) -> Option<&TeamGame> {
+        self.game.as_ref()
+    }
+
+    pub fn side_to_move(&self) -> Option<chess::Color> {
+        self.game.as_ref().map(|game| game.board.side_to_move())
+    }
+
+    /// Each team has its own vote pool, so each gets its own game id.
+    pub fn game_id(&self, color: chess::Color) -> GameId {
+        format!("team{}-{}", self.game_number, color_name(color))
+    }
+
+    pub fn color_for_game_id(&self, game_id: &str) -> Option<chess::Color> {
+        [chess::Color::White, chess::Color::Black]
+            .into_iter()
+            .find(|color| self.game_id(*color) == game_id)
+    }
+
+    pub fn votes_mut(&mut self, game_id: &str) -> Option<&mut VoteTracker> {
+        let color = self.color_for_game_id(game_id)?;
+        Some(&mut self.team_mut(color).votes)
+    }
+
+    pub fn convert_move(&self, chess_move: String) -> Option<ChessMove> {
+        let game = self.game.as_ref()?;
+        let chess_move = ChessMove::from_str(&chess_move).ok()?;
+        game.board.legal(chess_move).then_some(chess_move)
+    }
+
+    /// Whether a draw vote from the team counts: it can always accept the other team's offer,
+    /// but can't repeat its own while it stands or after it's been turned down.
+    pub fn can_offer_draw(&self, color: chess::Color) -> bool {
+        let Some(game) = &self.game else {
+            return false;
+        };
+
+        match game.draw_offer {
+            Some(offered_by) if offered_by != color => true,
+            Some(_) => false,
+            None => !game.draws_declined.contains(&color),
+        }
+    }
+
+    /// Offers a draw, returning true if the other team had already offered one.
+    pub fn offer_draw(&mut self, color: chess::Color) -> bool {
+        let Some(game) = &mut self.game else {
+            return false;
+        };
+
+        if game.draw_offer == Some(!color) {
+            return true;
+        }
+
+        game.draw_offer = color.into();
+        false
+    }
+
+    /// Plays the move, returning the result if it ended the game.
+    pub fn play_move(&mut self, chess_move: ChessMove) -> Option<TeamResult> {
+        let game = self.game.as_mut()?;
+        let mover = game.board.side_to_move();
+        let is_capture = game.board.piece_on(chess_move.get_dest()).is_some();
+        let is_pawn_move = game.board.piece_on(chess_move.get_source()) == Some(Piece::Pawn);
+
+        game.board = game.board.make_move_new(chess_move);
+        game.move_history.push(chess_move.to_string());
+        game.last_move = chess_move.into();
+
+        game.halfmove_clock = if is_capture || is_pawn_move { 0 } else { game.halfmove_clock + 1 };
+        let repetitions = game.repetitions.entry(game.board.get_hash()).or_default();
+        *repetitions += 1;
+
+        // An offer only stands until the other team replies with a move.
+        if game.draw_offer == Some(!mover) {
+            game.draw_offer = None;
+            game.draws_declined.insert(!mover);
+        }
+
+        match game.board.status() {
+            BoardStatus::Checkmate => TeamResult::Win(mover).into(),
+            BoardStatus::Stalemate => TeamResult::Draw.into(),
+            BoardStatus::Ongoing if *repetitions >= 3 => TeamResult::Draw.into(),
+            BoardStatus::Ongoing if game.halfmove_clock >= FIFTY_MOVE_PLIES => {
+                TeamResult::Draw.into()
+            }
+            BoardStatus::Ongoing if is_insufficient_material(&game.board) => {
+                TeamResult::Draw.into()
+            }
+            BoardStatus::Ongoing => None,
+        }
+    }
+
+    /// The team game dressed up as a regular game, so the stream can show it as usual.
+    pub fn game_view(&self) -> Option<Game> {
+        let game = self.game.as_ref()?;
+
+        let player = |team: &Team| Player {
+            name: format!("{} team ({})", capitalise(color_name(team.color)), team.members.len()),
+            color: team.color,
+            rating: None,
+            timer: Timer::new(0),
+        };
+
+        let game = Game {
+            game_id: self.game_id(game.board.side_to_move()),
+            speed: Speed::Blitz,
+            variant: VariantKey::Standard,
+            rated: false,
+            timestamp: game.started,
+            clock_settings: None,
+            position: Position::from_board(game.board),
+            start_position: Position::default(),
+            initial_fen: None,
+            move_history: game.move_history.clone(),
+            move_clocks: Vec::new(),
+            last_move: game.last_move,
+            is_our_turn: game.board.side_to_move() == chess::Color::White,
+            us: player(&self.white),
+            opponent: player(&self.black),
+            timers_started: false,
+            finished: false,
+            started_at: SystemTime::now(),
+            status: "started".to_string(),
+            winner: None,
+        };
+
+        game.into()
+    }
+
+    pub fn notice(&self) -> Notice {
+        let lines = vec![
+            "Team battle! Pick a side:".to_string(),
+            "!join white or !join black".to_string(),
+            "".to_string(),
+            format!("White {} - {} Black", self.white.wins, self.black.wins),
+            format!("Draws: {}", self.draws),
+        ];
+
+        Notice { lines }
+    }
+
+    pub fn team_mut(&mut self, color: chess::Color) -> &mut Team {
+        match color {
+            chess::Color::White => &mut self.white,
+            chess::Color::Black => &mut self.black,
+        }
+    }
+}
+
+impl Team {
+    pub fn new(color: chess::Color, event_sender: EventSender) -> Self {
+        Self {
+            color,
+            members: Default::default(),
+            wins: 0,
+            votes: VoteTracker::new(&Speed::Blitz, event_sender),
+        }
+    }
+}
+
+pub fn color_name(color: chess::Color) -> &'static str {
+    match color {
+        chess::Color::White => "white",
+        chess::Color::Black => "black",
+    }
+}
+
+/// Bare kings, a single minor piece, or bishops all on the same colour of square.
+fn is_insufficient_material(board: &chess::Board) -> bool {
+    let heavy =
+        *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    if heavy.popcnt() > 0 {
+        return false;
+    }
+
+    let knights = board.pieces(Piece::Knight).popcnt();
+    let bishops = *board.pieces(Piece::Bishop);
+    if knights + bishops.popcnt() <= 1 {
+        return true;
+    }
+
+    let light_bishops = (bishops & BitBoard::new(LIGHT_SQUARES)).popcnt();
+    knights == 0 && (light_bishops == 0 || light_bishops == bishops.popcnt())
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::engine::votes::Username;
+use crate::twitch::command::GameMode;
+
+/// Viewers waiting to play chat from their own Lichess accounts.
+pub struct ViewerQueue {
+    enabled: bool,
+    entries: VecDeque<ViewerEntry>,
+    max_length: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ViewerEntry {
+    /// Who asked on Twitch.
+    pub viewer: Username,
+    pub lichess_id: String,
+    pub lichess_name: String,
+    /// Their rating for each game mode in the time control catalogue they've played.
+    pub ratings: HashMap<GameMode, u32>,
+}
+
+impl ViewerQueue {
+    pub fn new(enabled: bool, max_length: usize) -> Self {
+        Self { enabled, entries: Default::default(), max_length }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn entries(&self) -> &VecDeque<ViewerEntry> {
+        &self.entries
+    }
+
+    /// Whether the viewer or the Lichess account is already queued.
+    pub fn contains(&self, viewer: &str, lichess_id: &str) -> bool {
+        let lichess_id = lichess_id.to_lowercase();
+        self.entries.iter().any(|entry| entry.viewer == viewer || entry.lichess_id == lichess_id)
+    }
+
+    /// Adds the entry to the back of the queue, returning false if there's no room for it.
+    pub fn push(&mut self, entry: ViewerEntry) -> bool {
+        if self.entries.len() >= self.max_length || self.contains(&entry.viewer, &entry.lichess_id)
+        {
+            return false;
+        }
+
+        log::info!("[ViewerQueue] {} queued as {}", &entry.viewer, &entry.lichess_name);
+        self.entries.push_back(entry);
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<ViewerEntry> {
+        self.entries.pop_front()
+    }
+}
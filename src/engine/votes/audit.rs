@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::lichess::game::GameId;
+
+use super::game::Vote;
+use super::Username;
+
+/// Keeps a record of every ballot and every decision made for each game.
+/// This is the evidence for when chat insists that their move wasn't played.
+#[derive(Default)]
+pub struct AuditLog {
+    games: HashMap<GameId, GameAudit>,
+    current_game_id: Option<GameId>,
+}
+
+#[derive(Serialize)]
+pub struct GameAudit {
+    pub game_id: GameId,
+    pub decisions: Vec<Decision>,
+    #[serde(skip)]
+    pending: Vec<Ballot>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Ballot {
+    pub user: Username,
+    pub text: String,
+    pub vote: Option<String>,
+    pub timestamp: u64,
+    pub changed_vote: bool,
+    pub accepted: bool,
+}
+
+#[derive(Serialize)]
+pub struct Decision {
+    pub timestamp: u64,
+    pub ballots: Vec<Ballot>,
+    pub tally: HashMap<String, u32>,
+    pub winning_vote: Option<String>,
+    pub played: Option<String>,
+    pub reason: CloseReason,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// The vote timer ran out and the top vote was played.
+    TimerExpired,
+    /// The vote timer ran out without any votes, so a random move was played.
+    NoVotes,
+    /// Chat voted to delay, so the window was reopened.
+    Delayed,
+    /// The window was thrown away, e.g. the game ended or focus switched to another game.
+    Discarded,
+}
+
+impl AuditLog {
+    pub fn set_game(&mut self, game_id: GameId) {
+        self.games
+            .entry(game_id.to_string())
+            .or_insert_with(|| GameAudit::new(game_id.to_string()));
+        self.current_game_id = game_id.into();
+    }
+
+    pub fn record_ballot(
+        &mut self,
+        user: Username,
+        text: String,
+        vote: Option<Vote>,
+        changed_vote: bool,
+        accepted: bool,
+    ) {
+        let Some(game) = self.current_game_mut() else {
+            log::warn!("[AuditLog] Ballot from {} received without a current game", &user);
+            return;
+        };
+
+        let ballot = Ballot {
+            user,
+            text,
+            vote: vote.map(|vote| vote.to_string()),
+            timestamp: unix_millis(),
+            changed_vote,
+            accepted,
+        };
+
+        game.pending.push(ballot);
+    }
+
+    pub fn record_decision(
+        &mut self,
+        tally: HashMap<String, u32>,
+        winning_vote: Option<Vote>,
+        played: Option<String>,
+        reason: CloseReason,
+    ) {
+        let Some(game) = self.current_game_mut() else {
+            return;
+        };
+
+        game.close_window(tally, winning_vote.map(|vote| vote.to_string()), played, reason);
+    }
+
+    pub fn has_pending_ballots(&self) -> bool {
+        self.current_game_id
+            .as_ref()
+            .and_then(|game_id| self.games.get(game_id))
+            .map(|game| !game.pending.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Removes the audit for a game, discarding any window that was still open.
+    pub fn finish_game(&mut self, game_id: &str) -> Option<GameAudit> {
+        let mut game = self.games.remove(game_id)?;
+
+        if !game.pending.is_empty() {
+            game.close_window(Default::default(), None, None, CloseReason::Discarded);
+        }
+
+        if self.current_game_id.as_deref() == Some(game_id) {
+            self.current_game_id = None;
+        }
+
+        game.into()
+    }
+
+    fn current_game_mut(&mut self) -> Option<&mut GameAudit> {
+        let game_id = self.current_game_id.as_ref()?;
+        self.games.get_mut(game_id)
+    }
+}
+
+impl GameAudit {
+    pub fn new(game_id: GameId) -> Self {
+        Self { game_id, decisions: Default::default(), pending: Default::default() }
+    }
+
+    /// Writes the audit as <directory>/<game id>.json.
+    pub fn write(&self, directory: &Path) -> Result<()> {
+        std::fs::create_dir_all(directory)?;
+
+        let path = directory.join(format!("{}.json", &self.game_id));
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+
+    fn close_window(
+        &mut self,
+        tally: HashMap<String, u32>,
+        winning_vote: Option<String>,
+        played: Option<String>,
+        reason: CloseReason,
+    ) {
+        let decision = Decision {
+            timestamp: unix_millis(),
+            ballots: std::mem::take(&mut self.pending),
+            tally,
+            winning_vote,
+            played,
+            reason,
+        };
+
+        self.decisions.push(decision);
+    }
+}
+
+pub fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
@@ -1,3 +1,4 @@
+use std::collections::{HashSet, VecDeque};
 use std::{collections::HashMap, time::Duration};
 
 use lichess_api::model::Speed;
@@ -14,12 +15,24 @@ use crate::{
     lichess::game::GameId,
 };
 
+use super::audit::{AuditLog, CloseReason, GameAudit};
 use super::Username;
 
+/// How many seconds of tallies are kept for the sparklines.
+const HISTORY_LENGTH: usize = 20;
+/// How many seconds back momentum is measured from.
+const MOMENTUM_WINDOW: usize = 5;
+
 pub struct VoteTracker {
     enabled: bool,
+    audit: AuditLog,
     delays: Delays,
     votes: HashMap<Username, Option<Vote>>,
+    /// Votes from spectators in the Lichess game chat, tallied separately.
+    spectator_votes: HashMap<Username, Vote>,
+    /// How much a spectator's vote counts compared to one from Twitch.
+    spectator_weight: f32,
+    history: VecDeque<HashMap<String, u32>>,
     vote_duration: Duration,
     vote_timer: Option<VoteTimer>,
     event_sender: EventSender,
@@ -40,39 +53,94 @@ pub enum Vote {
 
 impl VoteTracker {
     pub fn new(speed: &Speed, event_sender: EventSender) -> Self {
-        let (max_delays, vote_duration) = match speed {
-            Speed::UltraBullet => (3, 2),
-            Speed::Bullet => (5, 5),
-            Speed::Blitz => (6, 12),
-            Speed::Rapid => (8, 36),
-            Speed::Classical => (10, 72),
-            _ => (1, 1),
-        };
+        let (max_delays, vote_duration) = timing(speed);
 
         Self {
             enabled: false,
+            audit: Default::default(),
             delays: Delays::new(max_delays),
             votes: Default::default(),
-            vote_duration: Duration::from_secs(vote_duration),
+            spectator_votes: Default::default(),
+            spectator_weight: 1.0,
+            history: Default::default(),
+            vote_duration,
             vote_timer: None,
             event_sender,
         }
     }
 
-    pub fn add_vote(&mut self, user: Username, vote: Vote) {
+    /// Matches the vote timing to a new game's speed. Takes effect from the next vote.
+    pub fn set_speed(&mut self, speed: &Speed) {
+        let (max_delays, vote_duration) = timing(speed);
+        self.delays = Delays::new(max_delays);
+        self.vote_duration = vote_duration;
+    }
+
+    pub fn set_spectator_weight(&mut self, weight: f32) {
+        self.spectator_weight = weight;
+    }
+
+    pub fn add_vote(&mut self, user: Username, text: String, vote: Vote) {
+        let Some((user, text)) = self.accept_ballot(user, text, vote) else {
+            return;
+        };
+
+        let previous_vote = self.votes.insert(user.to_string(), vote.into()).flatten();
+        let changed_vote = previous_vote.map(|previous| previous != vote).unwrap_or(false);
+        self.audit.record_ballot(user, text, vote.into(), changed_vote, true);
+
+        self.event_sender.send_notification(Notification::GameVotesChanged);
+    }
+
+    pub fn add_spectator_vote(&mut self, user: Username, text: String, vote: Vote) {
+        let Some((user, text)) = self.accept_ballot(user, text, vote) else {
+            return;
+        };
+
+        let previous_vote = self.spectator_votes.insert(user.to_string(), vote);
+        let changed_vote = previous_vote.map(|previous| previous != vote).unwrap_or(false);
+        self.audit.record_ballot(user, text, vote.into(), changed_vote, true);
+
+        self.event_sender.send_notification(Notification::GameVotesChanged);
+    }
+
+    /// Hands the ballot back if it can be counted, otherwise records it as rejected.
+    fn accept_ballot(
+        &mut self,
+        user: Username,
+        text: String,
+        vote: Vote,
+    ) -> Option<(Username, String)> {
         if !self.enabled {
             log::warn!("Voting not currently enabled.");
-            return;
+            self.audit.record_ballot(user, text, vote.into(), false, false);
+            return None;
         }
 
         if !self.delays.can_delay() && vote == Vote::Delay {
             log::warn!("Can't delay.");
-            return;
+            self.audit.record_ballot(user, text, vote.into(), false, false);
+            return None;
         };
 
-        _ = self.votes.insert(user, vote.into());
+        (user, text).into()
+    }
 
-        self.event_sender.send_notification(Notification::GameVotesChanged);
+    /// Records a ballot that couldn't be parsed into a vote, e.g. an illegal move.
+    pub fn reject_vote(&mut self, user: Username, text: String) {
+        self.audit.record_ballot(user, text, None, false, false);
+    }
+
+    pub fn set_game(&mut self, game_id: GameId) {
+        self.audit.set_game(game_id);
+    }
+
+    pub fn record_decision(&mut self, reason: CloseReason, played: Option<String>) {
+        self.audit.record_decision(self.tally(), self.get_top_vote(), played, reason);
+    }
+
+    pub fn finish_audit(&mut self, game_id: &str) -> Option<GameAudit> {
+        self.audit.finish_game(game_id)
     }
 
     pub fn add_delay(&mut self) {
@@ -101,9 +169,11 @@ impl VoteTracker {
             let mut interval = tokio::time::interval(Duration::from_secs(1));
             for _ in 0..vote_duration.as_secs() {
                 interval.tick().await;
-                event_sender.send_notification(Notification::GameVotesChanged)
+                let game_id = game_id.to_string();
+                event_sender.send_notification(Notification::GameVotesTick { game_id })
             }
-            event_sender.send_notification(Notification::VotingFinished);
+            let notification = Notification::VotingFinished { game_id: game_id.to_string() };
+            event_sender.send_notification(notification);
             event_sender.send_action(Action::Lichess(LichessAction::make_move(game_id)));
         });
 
@@ -124,36 +194,91 @@ impl VoteTracker {
             delays: self.delays.clone(),
         };
 
-        for vote in self.votes.values() {
-            let Some(vote_string) = vote.map(|vote| vote.to_string()) else {
-                continue;
-            };
-
-            let Some(vote_stats) = game_votes.votes.get_mut(&vote_string) else {
-                let vote_stats = VoteStats {
+        // Momentum is measured against the tally from MOMENTUM_WINDOW seconds ago,
+        // or the oldest tally if voting hasn't been open that long.
+        let momentum_base = self
+            .history
+            .len()
+            .checked_sub(MOMENTUM_WINDOW)
+            .and_then(|index| self.history.get(index))
+            .or(self.history.front());
+
+        let tally = self.tally();
+        let spectator_tally = self.spectator_tally();
+        let vote_strings: HashSet<&String> = tally.keys().chain(spectator_tally.keys()).collect();
+
+        for vote_string in vote_strings {
+            let total_votes = tally.get(vote_string).copied().unwrap_or(0);
+            let spectator_votes = spectator_tally.get(vote_string).copied().unwrap_or(0);
+            let history = self
+                .history
+                .iter()
+                .map(|tally| tally.get(vote_string).copied().unwrap_or(0))
+                .chain(std::iter::once(total_votes))
+                .collect();
+
+            let mut vote_stats =
+                VoteStats { vote_changes: 0, total_votes, spectator_votes, history };
+
+            if let Some(base) = momentum_base {
+                let total_votes = base.get(vote_string).copied().unwrap_or(0);
+                let old = VoteStats {
                     vote_changes: 0,
-                    total_votes: 1,
+                    total_votes,
+                    spectator_votes: 0,
+                    history: Default::default(),
                 };
-                game_votes.votes.insert(vote_string, vote_stats);
-                continue;
-            };
+                VoteStats::update_changes(&old, &mut vote_stats);
+            }
 
-            vote_stats.total_votes += 1;
+            game_votes.votes.insert(vote_string.to_string(), vote_stats);
         }
 
         game_votes
     }
 
+    /// Spectators' votes count for less or more than Twitch chat's, depending on their weight.
     pub fn get_top_vote(&self) -> Option<Vote> {
-        let mut vote_counts = HashMap::<Vote, u32>::default();
+        let mut vote_counts = HashMap::<Vote, f32>::default();
 
-        for vote in self.votes.values() {
-            if let Some(vote) = vote {
-                vote_counts.entry(*vote).and_modify(|count| *count += 1).or_insert(0);
-            }
+        for vote in self.votes.values().flatten() {
+            *vote_counts.entry(*vote).or_default() += 1.0;
+        }
+
+        for vote in self.spectator_votes.values() {
+            *vote_counts.entry(*vote).or_default() += self.spectator_weight;
+        }
+
+        vote_counts.into_iter().max_by(|l, r| l.1.total_cmp(&r.1)).map(|(vote, _)| vote)
+    }
+
+    pub fn tally(&self) -> HashMap<String, u32> {
+        let mut tally = HashMap::<String, u32>::default();
+
+        for vote in self.votes.values().flatten() {
+            *tally.entry(vote.to_string()).or_default() += 1;
         }
 
-        vote_counts.iter().max_by_key(|e| e.1).map(|e| e.0.clone())
+        tally
+    }
+
+    pub fn spectator_tally(&self) -> HashMap<String, u32> {
+        let mut tally = HashMap::<String, u32>::default();
+
+        for vote in self.spectator_votes.values() {
+            *tally.entry(vote.to_string()).or_default() += 1;
+        }
+
+        tally
+    }
+
+    /// Snapshots the current tally. Called once a second while voting is open.
+    pub fn record_history(&mut self) {
+        self.history.push_back(self.tally());
+
+        while self.history.len() > HISTORY_LENGTH {
+            self.history.pop_front();
+        }
     }
 
     pub fn reset(&mut self) {
@@ -162,12 +287,32 @@ impl VoteTracker {
     }
 
     pub fn reset_voting(&mut self) {
+        if self.audit.has_pending_ballots() {
+            self.record_decision(CloseReason::Discarded, None);
+        }
+
         self.votes.clear();
+        self.spectator_votes.clear();
+        self.history.clear();
         self.vote_timer = None;
         self.event_sender.send_notification(Notification::GameVotesChanged);
     }
 }
 
+/// How many delays chat gets and how long each vote lasts at a speed.
+fn timing(speed: &Speed) -> (u8, Duration) {
+    let (max_delays, vote_seconds) = match speed {
+        Speed::UltraBullet => (3, 2),
+        Speed::Bullet => (5, 5),
+        Speed::Blitz => (6, 12),
+        Speed::Rapid => (8, 36),
+        Speed::Classical => (10, 72),
+        _ => (1, 1),
+    };
+
+    (max_delays, Duration::from_secs(vote_seconds))
+}
+
 impl ToString for Vote {
     fn to_string(&self) -> String {
         match self {
@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod game;
 pub mod settings;
 
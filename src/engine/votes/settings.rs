@@ -1,6 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{
+    config::TimeControl,
     engine::events::internal::{EventSender, Notification},
     twitch::command::GameMode,
     twitch::command::Setting,
@@ -9,34 +10,59 @@ use crate::{
 use super::Username;
 
 pub struct VoteTracker {
-    pub bullet: HashSet<Username>,
-    pub rapid: HashSet<Username>,
-    pub classical: HashSet<Username>,
+    /// The game modes on offer, in display order, and whether each is always on.
+    modes: Vec<(GameMode, bool)>,
+    votes: HashMap<GameMode, HashSet<Username>>,
+    /// Whether each voter wants Chess960 on or off.
+    chess960: HashMap<Username, bool>,
+    positions: HashMap<Username, String>,
     pub event_sender: EventSender,
 }
 
 #[derive(Default, Clone, Eq, PartialEq)]
 pub struct Settings {
-    pub game_modes: GameModes,
-    pub bullet: usize,
-    pub rapid: usize,
-    pub classical: usize,
+    pub game_modes: Vec<GameModeSetting>,
+    pub chess960: bool,
+    pub chess960_votes: usize,
+    /// Everyone who's voted Chess960 on or off.
+    pub chess960_total: usize,
+    /// The most voted for starting position, if anyone's voted for one.
+    pub position: Option<PositionSetting>,
+    /// Everyone who's voted for a game mode.
     pub total: usize,
 }
 
 #[derive(Clone, Eq, PartialEq)]
-pub struct GameModes {
-    pub bullet: bool,
-    pub rapid: bool,
-    pub classical: bool,
+pub struct PositionSetting {
+    pub key: String,
+    pub votes: usize,
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct GameModeSetting {
+    pub game_mode: GameMode,
+    pub always_on: bool,
+    pub on: bool,
+    pub votes: usize,
 }
 
 impl VoteTracker {
-    pub fn new(event_sender: EventSender) -> Self {
+    /// Offers a game mode for each speed in the catalogue.
+    pub fn new(catalogue: &[TimeControl], event_sender: EventSender) -> Self {
+        let mut modes = Vec::<(GameMode, bool)>::default();
+        for time_control in catalogue {
+            let game_mode = GameMode::from_speed(&time_control.speed);
+            match modes.iter_mut().find(|(mode, _)| *mode == game_mode) {
+                Some((_, always_on)) => *always_on |= time_control.always_on,
+                None => modes.push((game_mode, time_control.always_on)),
+            }
+        }
+
         Self {
-            bullet: Default::default(),
-            rapid: Default::default(),
-            classical: Default::default(),
+            modes,
+            votes: Default::default(),
+            chess960: Default::default(),
+            positions: Default::default(),
             event_sender,
         }
     }
@@ -44,15 +70,33 @@ impl VoteTracker {
     pub fn add_vote(&mut self, user: Username, setting: Setting, on: bool) {
         match setting {
             Setting::GameMode(game_mode) => self.add_game_mode_vote(user, game_mode, on),
+            Setting::Chess960 => {
+                self.chess960.insert(user, on);
+            }
+            Setting::StartingPosition(key) => {
+                if on {
+                    self.positions.insert(user, key);
+                } else {
+                    self.positions.remove(&user);
+                }
+            }
         }
 
         self.event_sender.send_notification(Notification::SettingsChanged);
     }
 
     pub fn remove_user(&mut self, user: &Username) {
-        self.bullet.remove(user);
-        self.rapid.remove(user);
-        self.classical.remove(user);
+        for set in self.votes.values_mut() {
+            set.remove(user);
+        }
+        self.chess960.remove(user);
+        self.positions.remove(user);
+    }
+
+    /// Starting positions are chosen one game at a time.
+    pub fn clear_position_votes(&mut self) {
+        self.positions.clear();
+        self.event_sender.send_notification(Notification::SettingsChanged);
     }
 
     pub fn settings(&self) -> Settings {
@@ -65,39 +109,51 @@ impl VoteTracker {
             ratio >= 0.5
         }
 
-        let bullet = self.bullet.len();
-        let rapid = self.rapid.len();
-        let classical = self.classical.len();
-
-        let mut all = HashSet::<String>::default();
-
-        for user in &self.bullet {
-            all.insert(user.to_string());
-        }
-        for user in &self.rapid {
-            all.insert(user.to_string());
+        // Each setting is decided among those who voted on it, so voting for a position doesn't
+        // count against every game mode.
+        let game_mode_voters: HashSet<&Username> = self.votes.values().flatten().collect();
+        let total = game_mode_voters.len();
+
+        let game_modes = self
+            .modes
+            .iter()
+            .map(|(game_mode, always_on)| {
+                let votes = self.votes.get(game_mode).map(|set| set.len()).unwrap_or(0);
+                GameModeSetting {
+                    game_mode: *game_mode,
+                    always_on: *always_on,
+                    on: *always_on || is_enabled(votes, total),
+                    votes,
+                }
+            })
+            .collect();
+
+        let chess960_votes = self.chess960.values().filter(|on| **on).count();
+        let chess960_total = self.chess960.len();
+        let chess960 = is_enabled(chess960_votes, chess960_total);
+
+        let mut position_votes = HashMap::<&String, usize>::default();
+        for key in self.positions.values() {
+            *position_votes.entry(key).or_default() += 1;
         }
-        for user in &self.classical {
-            all.insert(user.to_string());
-        }
-
-        let total = all.len();
-
-        let game_modes = GameModes {
-            bullet: is_enabled(bullet, total),
-            rapid: is_enabled(rapid, total),
-            classical: is_enabled(classical, total),
-        };
-
-        Settings { game_modes, bullet, rapid, classical, total }
+        // Ties go to the first alphabetically so the leader doesn't flicker.
+        let position = position_votes
+            .into_iter()
+            .max_by(|(l_key, l_votes), (r_key, r_votes)| {
+                l_votes.cmp(r_votes).then_with(|| r_key.cmp(l_key))
+            })
+            .map(|(key, votes)| PositionSetting { key: key.to_string(), votes });
+
+        Settings { game_modes, chess960, chess960_votes, chess960_total, position, total }
     }
 
     fn add_game_mode_vote(&mut self, user: Username, game_mode: GameMode, on: bool) {
-        let set = match game_mode {
-            GameMode::Bullet => &mut self.bullet,
-            GameMode::Rapid => &mut self.rapid,
-            GameMode::Classical => &mut self.classical,
-        };
+        if !self.modes.iter().any(|(mode, _)| *mode == game_mode) {
+            log::info!("Ignoring vote for {} - no time control uses it", game_mode.to_string());
+            return;
+        }
+
+        let set = self.votes.entry(game_mode).or_default();
 
         if on {
             set.insert(user.to_string());
@@ -108,6 +164,10 @@ impl VoteTracker {
 }
 
 impl Settings {
+    pub fn is_enabled(&self, game_mode: GameMode) -> bool {
+        self.game_modes.iter().any(|setting| setting.game_mode == game_mode && setting.on)
+    }
+
     pub fn lines(&self) -> Vec<String> {
         fn description(on: bool, count: usize, total: usize) -> String {
             if total == 0 || count == 0 {
@@ -122,21 +182,27 @@ impl Settings {
             }
         }
 
-        let bullet = description(self.game_modes.bullet, self.bullet, self.total);
-        let rapid = description(self.game_modes.rapid, self.rapid, self.total);
-        let classical = description(self.game_modes.classical, self.classical, self.total);
-
-        vec![
-            format!("Bullet: {}", bullet),
-            "Blitz: always on".to_owned(),
-            format!("Rapid: {}", rapid),
-            format!("Classical: {}", classical),
-        ]
-    }
-}
-
-impl Default for GameModes {
-    fn default() -> Self {
-        Self { bullet: true, rapid: true, classical: true }
+        let mut lines: Vec<String> = self
+            .game_modes
+            .iter()
+            .map(|setting| {
+                let description = if setting.always_on {
+                    "always on".to_string()
+                } else {
+                    description(setting.on, setting.votes, self.total)
+                };
+                format!("{}: {}", setting.game_mode.name(), description)
+            })
+            .collect();
+
+        let chess960 = description(self.chess960, self.chess960_votes, self.chess960_total);
+        lines.push(format!("Chess960: {}", chess960));
+
+        let position = match &self.position {
+            Some(position) => format!("{} ({})", position.key, position.votes),
+            None => "standard".to_string(),
+        };
+        lines.push(format!("Position: {}", position));
+        lines
     }
 }
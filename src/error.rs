@@ -19,6 +19,9 @@ pub enum Error {
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
@@ -7,9 +7,13 @@ use lichess_api::model::challenges::decline::Reason;
 use lichess_api::model::challenges::{ChallengeBase, ChallengeCreated, CreateChallenge};
 use lichess_api::model::users::User;
 use lichess_api::model::VariantKey;
+use tokio::task::JoinHandle;
 
+use crate::config::{AccountMode, TimeControl};
+use crate::engine::events::internal::{EventSender, Notification};
 use crate::error::Result;
 
+use crate::lichess::profile::PublicProfile;
 use crate::lichess::Context;
 
 pub struct Actor {
@@ -55,24 +59,57 @@ impl Actor {
         Ok(bots)
     }
 
+    pub async fn get_user(&self, username: &str) -> Result<User> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        type Request = lichess_api::model::users::public::GetRequest;
+        self.context
+            .api
+            .get_public_user_data(Request::new(username, false))
+            .await
+            .map_err(|e| crate::error::Error::LichessError(e))
+    }
+
+    pub async fn get_public_profile(&self, username: &str) -> Result<PublicProfile> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("https://lichess.org/api/user/{}", username);
+        let response = self
+            .context
+            .client
+            .get(url)
+            .bearer_auth(&self.context.access_token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+
     pub async fn create_challenge(
         &self,
         username: String,
-        limit: u32,
-        increment: u32,
+        time_control: &TimeControl,
+        variant: VariantKey,
+        fen: Option<String>,
     ) -> Result<ChallengeCreated> {
         tokio::time::sleep(Duration::from_millis(100)).await;
 
+        let (clock_limit, clock_increment) = match time_control.days {
+            Some(_) => (None, None),
+            None => (time_control.limit.into(), time_control.increment.into()),
+        };
         let base = ChallengeBase {
-            clock_limit: limit.into(),
-            clock_increment: increment.into(),
-            days: None,
-            variant: VariantKey::Standard,
-            fen: None,
+            clock_limit,
+            clock_increment,
+            days: time_control.days,
+            variant,
+            fen: fen.clone(),
         };
         let challenge = CreateChallenge {
             base,
-            rated: true,
+            // Lichess doesn't rate games from custom positions.
+            rated: fen.is_none(),
             keep_alive_stream: false,
             accept_by_token: None,
             message: None,
@@ -87,6 +124,50 @@ impl Actor {
             .map_err(|e| crate::error::Error::LichessError(e))
     }
 
+    /// Like create_challenge, but with our colour picked - which the API crate can't do.
+    pub async fn create_colored_challenge(
+        &self,
+        username: String,
+        time_control: &TimeControl,
+        variant: VariantKey,
+        fen: Option<String>,
+        color: chess::Color,
+    ) -> Result<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let variant = serde_json::to_value(&variant)?;
+        let color = if color == chess::Color::White { "white" } else { "black" };
+        let mut form = vec![
+            ("variant", variant.as_str().unwrap_or("standard").to_string()),
+            ("color", color.to_string()),
+            // Lichess doesn't rate games from custom positions.
+            ("rated", fen.is_none().to_string()),
+            ("rules", "noGiveTime,noRematch".to_string()),
+        ];
+        match time_control.days {
+            Some(days) => form.push(("days", days.to_string())),
+            None => {
+                form.push(("clock.limit", time_control.limit.to_string()));
+                form.push(("clock.increment", time_control.increment.to_string()));
+            }
+        }
+        if let Some(fen) = fen {
+            form.push(("fen", fen));
+        }
+
+        let url = format!("https://lichess.org/api/challenge/{}", username);
+        self.context
+            .client
+            .post(url)
+            .bearer_auth(&self.context.access_token)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
     pub async fn accept_challenge(&self, challenge_id: String) -> Result<bool> {
         log::info!("Accepting challenge: id {}", &challenge_id);
         tokio::time::sleep(Duration::from_millis(100)).await;
@@ -127,6 +208,10 @@ impl Actor {
         log::info!("Aborting game {}", &game_id);
         tokio::time::sleep(Duration::from_millis(100)).await;
 
+        if self.context.mode == AccountMode::Board {
+            return self.post_board_action(game_id, "abort").await;
+        }
+
         type Request = lichess_api::model::bot::abort::PostRequest;
         self.context
             .api
@@ -135,12 +220,38 @@ impl Actor {
             .map_err(|e| crate::error::Error::LichessError(e))
     }
 
+    /// Posts to the players' chat. The API crate has no request for it, so it's made directly.
+    pub async fn write_chat(&self, game_id: &str, text: &str) -> Result<()> {
+        log::info!("Writing to chat in game {}: {}", &game_id, &text);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!(
+            "https://lichess.org/api/{}/game/{}/chat",
+            self.context.mode.api_path(),
+            game_id
+        );
+        self.context
+            .client
+            .post(url)
+            .bearer_auth(&self.context.access_token)
+            .form(&[("room", "player"), ("text", text)])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
     pub async fn make_move(&self, game_id: &str, chess_move: chess::ChessMove) -> Result<bool> {
         log::info!("Making move {}", &game_id);
         tokio::time::sleep(Duration::from_millis(100)).await;
 
-        type Request = lichess_api::model::bot::r#move::PostRequest;
         let chess_move = chess_move.to_string();
+        if self.context.mode == AccountMode::Board {
+            return self.post_board_action(game_id, &format!("move/{}", chess_move)).await;
+        }
+
+        type Request = lichess_api::model::bot::r#move::PostRequest;
         self.context
             .api
             .bot_make_move(Request::new(&game_id, &chess_move, false))
@@ -152,6 +263,10 @@ impl Actor {
         log::info!("Offering to draw game {}", &game_id);
         tokio::time::sleep(Duration::from_millis(100)).await;
 
+        if self.context.mode == AccountMode::Board {
+            return self.post_board_action(game_id, "draw/yes").await;
+        }
+
         type Request = lichess_api::model::bot::draw::PostRequest;
         self.context
             .api
@@ -164,6 +279,10 @@ impl Actor {
         log::info!("Resigning game {}", &game_id);
         tokio::time::sleep(Duration::from_millis(100)).await;
 
+        if self.context.mode == AccountMode::Board {
+            return self.post_board_action(game_id, "resign").await;
+        }
+
         type Request = lichess_api::model::bot::resign::PostRequest;
         self.context
             .api
@@ -171,6 +290,71 @@ impl Actor {
             .await
             .map_err(|e| crate::error::Error::LichessError(e))
     }
+
+    /// Seeks an opponent in the lobby, which only the Board API can do. A real-time seek
+    /// lasts as long as its request, so a task holds it open until someone accepts it - the
+    /// game then starts as usual - or the duration runs out and SeekExpired is sent.
+    pub fn create_seek(
+        &self,
+        time_control: &TimeControl,
+        variant: VariantKey,
+        rating_range: Option<(u32, u32)>,
+        duration: Duration,
+        mut event_sender: EventSender,
+    ) -> Result<JoinHandle<()>> {
+        let variant = serde_json::to_value(&variant)?;
+        let mut form = vec![
+            ("variant", variant.as_str().unwrap_or("standard").to_string()),
+            ("rated", true.to_string()),
+            ("time", (time_control.limit as f32 / 60.0).to_string()),
+            ("increment", time_control.increment.to_string()),
+        ];
+        if let Some((low, high)) = rating_range {
+            form.push(("ratingRange", format!("{}-{}", low, high)));
+        }
+
+        log::info!("Seeking a {} game", time_control.label());
+        let request = self
+            .context
+            .client
+            .post("https://lichess.org/api/board/seek")
+            .bearer_auth(&self.context.access_token)
+            .form(&form);
+
+        Ok(tokio::task::spawn(async move {
+            let seek = async {
+                let mut response = request.send().await?.error_for_status()?;
+                while response.chunk().await?.is_some() {}
+                Ok::<(), reqwest::Error>(())
+            };
+
+            match tokio::time::timeout(duration, seek).await {
+                Ok(Ok(())) => log::info!("Seek was accepted"),
+                Ok(Err(e)) => {
+                    log::warn!("Seek failed: {}", e);
+                    event_sender.send_notification(Notification::SeekExpired);
+                }
+                Err(_) => {
+                    log::info!("Nobody accepted the seek");
+                    event_sender.send_notification(Notification::SeekExpired);
+                }
+            }
+        }))
+    }
+
+    /// Board API requests the API crate has no equivalent for, e.g. `move/e2e4` or `resign`.
+    async fn post_board_action(&self, game_id: &str, action: &str) -> Result<bool> {
+        let url = format!("https://lichess.org/api/board/game/{}/{}", game_id, action);
+        self.context
+            .client
+            .post(url)
+            .bearer_auth(&self.context.access_token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(true)
+    }
 }
 
 #[derive(Debug)]
@@ -199,6 +383,18 @@ impl Action {
         Self::Account(AccountAction::ChallengeRandomBot)
     }
 
+    pub fn queue_viewer(viewer: String, username: String) -> Self {
+        Self::Account(AccountAction::QueueViewer { viewer, username })
+    }
+
+    pub fn refresh_our_rating() -> Self {
+        Self::Account(AccountAction::RefreshOurRating)
+    }
+
+    pub fn fetch_opponent_profile(username: String) -> Self {
+        Self::Account(AccountAction::FetchOpponentProfile { username })
+    }
+
     pub fn abort(game_id: String) -> Self {
         Self::Game { game_id, action: GameAction::Abort }
     }
@@ -214,6 +410,10 @@ impl Action {
     pub fn resign(game_id: String) -> Self {
         Self::Game { game_id, action: GameAction::Move }
     }
+
+    pub fn write_chat(game_id: String, text: String) -> Self {
+        Self::Game { game_id, action: GameAction::WriteChat { text } }
+    }
 }
 
 #[derive(Debug)]
@@ -222,6 +422,9 @@ pub enum AccountAction {
     CancelChallenge { challenge_id: String },
     DeclineChallenge { challenge_id: String, reason: Reason },
     ChallengeRandomBot,
+    QueueViewer { viewer: String, username: String },
+    RefreshOurRating,
+    FetchOpponentProfile { username: String },
 }
 
 #[derive(Debug)]
@@ -230,4 +433,5 @@ pub enum GameAction {
     Move,
     OfferDraw,
     Resign,
+    WriteChat { text: String },
 }
@@ -1,12 +1,14 @@
+use std::collections::VecDeque;
+use std::time::Duration;
 use std::time::Instant;
-use std::{collections::HashMap, time::Duration};
 
 use lichess_api::model::{
     challenges::{decline::Reason, ChallengeJson, Status},
-    Title, VariantKey,
+    Speed, Title, VariantKey,
 };
 use tokio::task::JoinHandle;
 
+use crate::config::Inbound;
 use crate::engine::events::internal::Action;
 use crate::engine::events::internal::EventSender;
 use crate::engine::events::internal::Notification;
@@ -14,11 +16,39 @@ use crate::lichess::action::Action as LichessAction;
 
 pub type ChallengeId = String;
 
+/// Why an outbound challenge ended without a game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChallengeFailure {
+    Declined,
+    /// Includes the opponent being offline - either way, no answer.
+    TimedOut,
+}
+
+/// Who a challenge was with. Only bots' answers and games say how reliable they are, how
+/// wide chat's difficulty band needs to be or how strong chat is.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ChallengeOrigin {
+    #[default]
+    Bot,
+    /// A viewer from the `!challengeme` queue.
+    Viewer,
+    /// Another player challenged us.
+    Inbound,
+}
+
 const MAX_OUTBOUND_CHALLENGE_WAIT_TIME: Duration = Duration::from_secs(20);
 
 pub struct ChallengeManager {
     our_id: String,
     outbound: Option<OutboundChallenge>,
+    /// Who the latest outbound challenge was sent to.
+    outbound_origin: ChallengeOrigin,
+    /// Our latest accepted outbound challenge, whose game may not have started yet.
+    accepted_outbound: Option<(ChallengeId, ChallengeOrigin)>,
+    inbound: VecDeque<Challenge>,
+    /// The inbound challenge being accepted, until its game starts or accepting it fails.
+    accepting: Option<ChallengeId>,
+    policy: Inbound,
     event_sender: EventSender,
 }
 
@@ -34,23 +64,97 @@ pub struct Challenge {
 }
 
 impl ChallengeManager {
-    pub fn new(our_id: String, event_sender: EventSender) -> Self {
-        Self { our_id, outbound: Default::default(), event_sender }
+    pub fn new(our_id: String, policy: Inbound, event_sender: EventSender) -> Self {
+        Self {
+            our_id,
+            outbound: Default::default(),
+            outbound_origin: Default::default(),
+            accepted_outbound: None,
+            inbound: Default::default(),
+            accepting: None,
+            policy,
+            event_sender,
+        }
+    }
+
+    /// Accepted challenges waiting for the current game to finish, oldest first.
+    pub fn inbound(&self) -> &VecDeque<Challenge> {
+        &self.inbound
+    }
+
+    /// Takes the longest waiting challenge to accept. No more are handed out until its game
+    /// starts or accepting it fails.
+    pub fn next_inbound(&mut self) -> Option<ChallengeId> {
+        if self.accepting.is_some() {
+            return None;
+        }
+
+        let challenge = self.inbound.pop_front()?;
+        let challenge_id = challenge.challenge.base.id.to_string();
+        self.accepting = challenge_id.clone().into();
+        self.event_sender.send_notification(Notification::InboundQueueChanged);
+        challenge_id.into()
+    }
+
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.is_some()
+    }
+
+    pub fn accept_failed(&mut self) {
+        self.accepting = None;
+    }
+
+    /// Clears whatever led to the game. Any other outbound challenge is cancelled, so we can't
+    /// end up in two games at once.
+    pub fn game_started(&mut self, game_id: &str) {
+        self.accepting = None;
+
+        let is_ours = match &self.outbound {
+            Some(outbound) => outbound.challenge.challenge.base.id == game_id,
+            None => false,
+        };
+        if is_ours {
+            // Cancelling a challenge whose game has started would abort the game.
+            if let Some(outbound) = self.outbound.take() {
+                outbound.cancel_handle.abort();
+            }
+        } else {
+            self.cancel_outbound();
+        }
     }
 
     pub fn outbound(&self) -> &Option<OutboundChallenge> {
         &self.outbound
     }
 
+    pub fn record_outbound_origin(&mut self, origin: ChallengeOrigin) {
+        self.outbound_origin = origin;
+    }
+
+    /// Who the challenge a game came from was with. None if it didn't come from one we know
+    /// of, e.g. it was from a seek or before a restart.
+    pub fn game_origin(&self, game_id: &str) -> Option<ChallengeOrigin> {
+        let outbound_id =
+            self.outbound.as_ref().map(|outbound| &outbound.challenge.challenge.base.id);
+        if outbound_id.map_or(false, |id| id == game_id) {
+            return self.outbound_origin.into();
+        }
+
+        match (&self.accepted_outbound, &self.accepting) {
+            (Some((challenge_id, origin)), _) if challenge_id == game_id => Some(*origin),
+            (_, Some(challenge_id)) if challenge_id == game_id => ChallengeOrigin::Inbound.into(),
+            _ => None,
+        }
+    }
+
     pub fn cancel_outbound(&mut self) {
-        if let Some(outbound) = &self.outbound {
+        if let Some(outbound) = self.outbound.take() {
             outbound.cancel_handle.abort();
 
-            // let challenge_id = outbound.challenge.challenge.base.id.to_string();
-            // let action = Action::Lichess(LichessAction::cancel_challenge(challenge_id));
-            // self.event_sender.send_action(action);
+            let challenge_id = outbound.challenge.challenge.base.id.to_string();
+            let action = Action::Lichess(LichessAction::cancel_challenge(challenge_id));
+            self.event_sender.send_action(action);
         }
-        self.outbound = None;
     }
 
     pub fn process_challenge(&mut self, challenge: ChallengeJson) {
@@ -72,6 +176,7 @@ impl ChallengeManager {
         let challenger = challenge.base.challenger.user.id.to_string();
 
         if challenger != self.our_id {
+            self.process_inbound_challenge(challenge);
             return;
         }
 
@@ -85,29 +190,124 @@ impl ChallengeManager {
         self.outbound = OutboundChallenge::new(challenge, handle).into();
     }
 
+    fn process_inbound_challenge(&mut self, challenge: ChallengeJson) {
+        let challenge_id = challenge.base.id.to_string();
+
+        if !self.policy.enabled {
+            log::info!("Ignoring inbound challenge {} - inbound challenges are off", &challenge_id);
+            return;
+        }
+
+        let reason = match self.decline_reason(&challenge) {
+            Some(reason) => Some(reason),
+            None if self.inbound.len() >= self.policy.max_queue => Some(Reason::Later),
+            None => None,
+        };
+
+        if let Some(reason) = reason {
+            log::info!("Declining inbound challenge {}: {:?}", &challenge_id, &reason);
+            let action = Action::Lichess(LichessAction::decline_challenge(challenge_id, reason));
+            self.event_sender.send_action(action);
+            return;
+        }
+
+        log::info!("Queueing inbound challenge {}", &challenge_id);
+        self.inbound.push_back(Challenge::new(challenge));
+        self.event_sender.send_notification(Notification::InboundQueueChanged);
+    }
+
+    /// Checks an inbound challenge against the policy, returning why it should be declined.
+    fn decline_reason(&self, challenge: &ChallengeJson) -> Option<Reason> {
+        let policy = &self.policy;
+        let base = &challenge.base;
+
+        if !policy.variants.contains(&base.variant.key) {
+            return if policy.variants == vec![VariantKey::Standard] {
+                Reason::Standard.into()
+            } else {
+                Reason::Variant.into()
+            };
+        }
+
+        let speed = speed_rank(&base.speed);
+        let allowed: Vec<u8> = policy.speeds.iter().map(speed_rank).collect();
+        if !allowed.contains(&speed) {
+            return match (allowed.iter().min(), allowed.iter().max()) {
+                (Some(fastest), _) if speed < *fastest => Reason::TooFast.into(),
+                (_, Some(slowest)) if speed > *slowest => Reason::TooSlow.into(),
+                _ => Reason::TimeControl.into(),
+            };
+        }
+
+        if base.rated && !policy.rated {
+            return Reason::Casual.into();
+        }
+        if !base.rated && !policy.casual {
+            return Reason::Rated.into();
+        }
+
+        let is_bot = matches!(base.challenger.user.title, Some(Title::Bot));
+        if is_bot && !policy.bots {
+            return Reason::NoBot.into();
+        }
+        if !is_bot && !policy.humans {
+            return Reason::OnlyBot.into();
+        }
+
+        let rating = base.challenger.rating.unwrap_or(0);
+        if rating < policy.min_rating || rating > policy.max_rating {
+            return Reason::Generic.into();
+        }
+
+        None
+    }
+
     fn process_challenge_offline(&mut self, challenge: ChallengeJson) {
         log::info!("Challenge opponent offline: {}", challenge.base.id);
-        self.nullify_challenge(challenge);
+        self.nullify_unanswered_challenge(challenge, ChallengeFailure::TimedOut);
     }
 
     pub fn process_challenge_canceled(&mut self, challenge: ChallengeJson) {
         log::info!("Challenge canceled: {}", challenge.base.id);
-        self.nullify_challenge(challenge);
+        self.nullify_unanswered_challenge(challenge, ChallengeFailure::TimedOut);
     }
 
     pub fn process_challenge_declined(&mut self, challenge: ChallengeJson) {
         log::info!("Challenge declined: {}", challenge.base.id);
-        self.nullify_challenge(challenge);
+        self.nullify_unanswered_challenge(challenge, ChallengeFailure::Declined);
     }
 
     fn process_challenge_accepted(&mut self, challenge: ChallengeJson) {
         log::info!("Challenge accepted: {}", challenge.base.id);
-        self.nullify_challenge(challenge);
+        let challenge_id = challenge.base.id.to_string();
+        if self.nullify_challenge(challenge) {
+            self.accepted_outbound = (challenge_id, self.outbound_origin).into();
+        }
+    }
+
+    /// Nullifies a challenge that ended without a game, e.g. declined or timed out.
+    fn nullify_unanswered_challenge(
+        &mut self,
+        challenge: ChallengeJson,
+        failure: ChallengeFailure,
+    ) {
+        if self.nullify_challenge(challenge) {
+            let origin = self.outbound_origin;
+            let notification = Notification::OutboundChallengeUnanswered { failure, origin };
+            self.event_sender.send_notification(notification);
+        }
     }
 
-    pub fn nullify_challenge(&mut self, challenge: ChallengeJson) {
+    /// Returns true if the challenge was our outbound one.
+    pub fn nullify_challenge(&mut self, challenge: ChallengeJson) -> bool {
         let mut is_outbound = false;
 
+        let queue_length = self.inbound.len();
+        self.inbound.retain(|inbound| inbound.challenge.base.id != challenge.base.id);
+        if self.inbound.len() != queue_length {
+            self.event_sender.send_notification(Notification::InboundQueueChanged);
+        }
+
         if let Some(outbound) = &self.outbound {
             if challenge.base.id == outbound.challenge.challenge.base.id {
                 self.event_sender.send_notification(Notification::OutboundChallengeNullified);
@@ -119,6 +319,8 @@ impl ChallengeManager {
         if is_outbound {
             self.outbound = None;
         }
+
+        is_outbound
     }
 }
 
@@ -132,4 +334,23 @@ impl Challenge {
     pub fn new(challenge: ChallengeJson) -> Self {
         Self { challenge, timestamp: std::time::Instant::now() }
     }
+
+    /// The challenger's name and rating, as shown on stream.
+    pub fn challenger(&self) -> String {
+        let challenger = &self.challenge.base.challenger;
+        let rating = challenger.rating.map(|r| r.to_string()).unwrap_or("????".to_string());
+        format!("{} ({})", challenger.user.name, rating)
+    }
+}
+
+/// Orders speeds from fastest to slowest.
+fn speed_rank(speed: &Speed) -> u8 {
+    match speed {
+        Speed::UltraBullet => 0,
+        Speed::Bullet => 1,
+        Speed::Blitz => 2,
+        Speed::Rapid => 3,
+        Speed::Classical => 4,
+        Speed::Correspondence => 5,
+    }
 }
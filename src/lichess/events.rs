@@ -2,15 +2,24 @@ use async_std::stream::StreamExt;
 use crossbeam_channel::Sender;
 use lichess_api::model::bot;
 use std::collections::HashMap;
+use std::time::Instant;
 use tokio::task::JoinHandle;
 
+use crate::config::AccountMode;
 use crate::error::Result;
 use crate::lichess::Context;
 
 #[derive(Debug)]
 pub enum Event {
-    AccountEvent { event: bot::stream::events::Event },
-    GameEvent { game_id: String, event: bot::stream::game::Event },
+    AccountEvent {
+        event: bot::stream::events::Event,
+    },
+    /// Lichess sends no time with its clocks, so they're taken as of when the event was read.
+    GameEvent {
+        game_id: String,
+        event: bot::stream::game::Event,
+        received_at: Instant,
+    },
 }
 
 pub struct EventManager {
@@ -84,6 +93,10 @@ impl EventManager {
         sender: Sender<Result<Event>>,
         game_id: &str,
     ) -> Result<JoinHandle<()>> {
+        if self.context.mode == AccountMode::Board {
+            return self.stream_board_game_events(sender, game_id).await;
+        }
+
         let request = bot::stream::game::GetRequest::new(game_id);
         let mut stream = self.context.api.bot_stream_board_state(request).await?;
 
@@ -92,11 +105,56 @@ impl EventManager {
 
         Ok(tokio::task::spawn(async move {
             while let Some(result) = stream.next().await {
+                let received_at = Instant::now();
                 let result = result
-                    .map(|event| Event::GameEvent { game_id: game_id.clone(), event })
+                    .map(|event| Event::GameEvent { game_id: game_id.clone(), event, received_at })
                     .map_err(|e| crate::error::Error::LichessError(e));
                 sender.send(result).unwrap_or_default();
             }
         }))
     }
+
+    /// The Board API's game stream sends the same events as the bot one, but the API crate
+    /// only has the bot request, so the NDJSON is read directly.
+    async fn stream_board_game_events(
+        &self,
+        sender: Sender<Result<Event>>,
+        game_id: &str,
+    ) -> Result<JoinHandle<()>> {
+        let url = format!("https://lichess.org/api/board/game/stream/{}", game_id);
+        let mut response = self
+            .context
+            .client
+            .get(url)
+            .bearer_auth(&self.context.access_token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let game_id = game_id.to_string();
+
+        Ok(tokio::task::spawn(async move {
+            let mut buffer = Vec::<u8>::new();
+            while let Ok(Some(chunk)) = response.chunk().await {
+                buffer.extend_from_slice(&chunk);
+                while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    // Empty lines are keep-alives.
+                    if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+                        continue;
+                    }
+
+                    let received_at = Instant::now();
+                    let result = serde_json::from_slice::<bot::stream::game::Event>(&line)
+                        .map(|event| Event::GameEvent {
+                            game_id: game_id.clone(),
+                            event,
+                            received_at,
+                        })
+                        .map_err(|e| crate::error::Error::JsonError(e));
+                    sender.send(result).unwrap_or_default();
+                }
+            }
+        }))
+    }
 }
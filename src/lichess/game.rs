@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::time::Duration;
 use std::time::Instant;
+use std::time::SystemTime;
 
 use chess::BoardStatus;
 use chess::ChessMove;
@@ -9,6 +9,7 @@ use chess::ChessMove;
 use lichess_api::model::board::stream::events::GameEventInfo;
 use lichess_api::model::Color;
 use lichess_api::model::Speed;
+use lichess_api::model::VariantKey;
 
 use lichess_api::model::board::stream::game::GameFull;
 use lichess_api::model::board::stream::game::GameState;
@@ -19,6 +20,7 @@ use crate::engine::events::internal::Action;
 use crate::engine::events::internal::EventSender;
 use crate::engine::events::internal::GameNotification;
 use crate::engine::events::internal::Notification;
+use crate::lichess::position::Position;
 use crate::stream::audio::Clip;
 use crate::stream::model::ClockSettings;
 use crate::stream::model::Player;
@@ -28,32 +30,54 @@ pub type GameId = String;
 
 pub struct GameManager {
     our_id: String,
+    max_games: usize,
     games: HashMap<GameId, Game>,
     last_finished_game: Option<Game>,
     current_game_id: Option<GameId>,
     event_sender: EventSender,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameOutcome {
+    Win,
+    Loss,
+    Draw,
+}
+
 #[derive(Clone)]
 pub struct Game {
     pub game_id: GameId,
     pub speed: Speed,
+    pub variant: VariantKey,
+    pub rated: bool,
     pub timestamp: Instant,
     pub clock_settings: Option<ClockSettings>,
-    pub board: chess::Board,
+    pub position: Position,
+    /// Where the moves are replayed from.
+    pub start_position: Position,
+    /// The starting FEN as Lichess sent it, which keeps the Chess960 castling rights that
+    /// `start_position`'s own FEN loses. None when the game started from the usual position.
+    pub initial_fen: Option<String>,
     pub move_history: Vec<String>,
+    /// The mover's clock in milliseconds after each move, where Lichess told us it.
+    pub move_clocks: Vec<Option<u64>>,
     pub last_move: Option<chess::ChessMove>,
     pub is_our_turn: bool,
     pub us: Player,
     pub opponent: Player,
     pub timers_started: bool,
     pub finished: bool,
+    pub started_at: SystemTime,
+    /// Lichess' status, e.g. "started", "mate" or "outoftime".
+    pub status: String,
+    pub winner: Option<chess::Color>,
 }
 
 impl GameManager {
-    pub fn new(our_id: String, event_sender: EventSender) -> Self {
+    pub fn new(our_id: String, max_games: usize, event_sender: EventSender) -> Self {
         Self {
             our_id,
+            max_games,
             games: Default::default(),
             last_finished_game: Default::default(),
             current_game_id: Default::default(),
@@ -66,7 +90,15 @@ impl GameManager {
     }
 
     pub fn convert_move(&mut self, chess_move: String) -> Option<chess::ChessMove> {
-        let Some(game) = self.current_game() else {
+        let Some(game_id) = self.current_game().map(|game| game.game_id.to_string()) else {
+            return None;
+        };
+
+        self.convert_move_in_game(&game_id, chess_move)
+    }
+
+    pub fn convert_move_in_game(&self, game_id: &str, chess_move: String) -> Option<ChessMove> {
+        let Some(game) = self.games.get(game_id) else {
             return None;
         };
 
@@ -74,7 +106,7 @@ impl GameManager {
             return None;
         };
 
-        if game.board.legal(uci_move) {
+        if game.position.is_legal(uci_move) {
             uci_move.into()
         } else {
             None
@@ -100,19 +132,14 @@ impl GameManager {
         self.games.get(current_game_id)
     }
 
-    pub fn oldest_game_id(&self) -> Option<String> {
-        if self.games.is_empty() {
-            return None;
-        }
-
-        self.games
-            .iter()
-            .min_by(|l, r| l.1.timestamp.cmp(&r.1.timestamp))
-            .map(|(id, _)| id.to_string())
+    /// Correspondence games aren't counted - they're played alongside everything else.
+    pub fn active_game_count(&self) -> usize {
+        self.games.values().filter(|game| !game.finished && !game.is_correspondence()).count()
     }
 
-    pub fn advance_clocks(&mut self, duration: Duration) {
-        self.games.iter_mut().for_each(|(_, game)| game.elapse_time(duration.as_millis() as u64));
+    /// Games that could be put on stream - correspondence games are shown in their own panel.
+    pub fn active_games(&self) -> Vec<&Game> {
+        self.games.values().filter(|game| !game.finished && !game.is_correspondence()).collect()
     }
 
     pub fn switch_game(&mut self, game_id: &str) {
@@ -146,13 +173,14 @@ impl GameManager {
     }
 
     pub fn process_game_start(&mut self, game_info: &GameEventInfo) {
-        if self.current_game_id.is_some() {
-            return;
-        }
-
         let game_id = game_info.game_id.clone();
         let game = Game::from_game_start(game_info);
 
+        let is_full = self.current_game_id.is_some() && self.active_game_count() >= self.max_games;
+        if is_full && !game.is_correspondence() {
+            return;
+        }
+
         let None = self.games.insert(game_id.clone(), game) else {
             log::warn!("[GameManager] Evicted game {} during process game start", &game_id);
             return;
@@ -167,6 +195,7 @@ impl GameManager {
         if let Some(game) = self.games.get_mut(&game_id) {
             game.process_game_info(game_info);
             game.finished = true;
+            game.run_clocks();
         } else {
             log::warn!("[GameManager] Failed to find game {} during process game finish", &game_id);
         }
@@ -182,7 +211,7 @@ impl GameManager {
         // };
     }
 
-    pub fn process_game_full(&mut self, game_full: &GameFull) {
+    pub fn process_game_full(&mut self, game_full: &GameFull, received_at: Instant) {
         let game_id = &game_full.id;
         let Some(game) = self.games.get_mut(game_id) else {
             log::warn!("[GameManager] Failed to find game {} during process game full", game_id);
@@ -190,7 +219,7 @@ impl GameManager {
         };
 
         // I'm assuming here that GameFull has all necessary data - therefore we can override.
-        *game = Game::from_game_full(&self.our_id, game_full);
+        *game = Game::from_game_full(&self.our_id, game_full, received_at);
 
         if game.finished {
             let current_game_id = self.current_game_id.clone().unwrap_or("".to_string());
@@ -211,7 +240,12 @@ impl GameManager {
         self.event_sender.send_notification(notification);
     }
 
-    pub fn process_game_update(&mut self, game_id: &str, game_state: &GameState) {
+    pub fn process_game_update(
+        &mut self,
+        game_id: &str,
+        game_state: &GameState,
+        received_at: Instant,
+    ) {
         let Some(game) = self.games.get_mut(game_id) else {
             log::warn!("[GameManager] Failed to find game {} during process game update", &game_id);
             return;
@@ -223,12 +257,15 @@ impl GameManager {
             false
         };
 
-        let previous_board = game.board.clone();
-        game.process_game_state(&game_state);
+        let previous_board = game.position.board.clone();
+        let was_finished = game.finished;
+        game.process_game_state(&game_state, received_at);
 
         if is_current_game {
             if let Some(last_move) = game.last_move {
-                let clip = if previous_board.piece_on(last_move.get_dest()).is_some() {
+                // In Chess960 castling lands the king on its own rook, which isn't a capture.
+                let mover = previous_board.side_to_move();
+                let clip = if previous_board.color_on(last_move.get_dest()) == Some(!mover) {
                     Clip::Capture
                 } else {
                     Clip::Move
@@ -238,16 +275,18 @@ impl GameManager {
             }
         }
 
+        if game.finished && !was_finished {
+            let notification = GameNotification::GameOutcome {
+                game_id: game_id.to_string(),
+                outcome: GameOutcome::from_game_state(game.us.color, game_state),
+            };
+            self.event_sender.send_notification(Notification::Game(notification));
+        }
+
         if game.finished {
             if is_current_game {
                 let clip = if let Some(winner) = &game_state.winner {
-                    let our_color = match game.us.color {
-                        chess::Color::White => "white",
-                        chess::Color::Black => "black",
-                    }
-                    .to_string();
-
-                    if *winner == our_color {
+                    if *winner == color_name(game.us.color) {
                         Clip::Win
                     } else {
                         Clip::Loss
@@ -279,7 +318,7 @@ impl GameManager {
 
         self.event_sender.clone().send_notification(notification);
 
-        if game.board != previous_board {
+        if game.position.board != previous_board {
             let notification = GameNotification::PlayerMoved {
                 game_id: game.game_id.to_string(),
                 was_us: !game.is_our_turn,
@@ -297,7 +336,7 @@ impl Game {
     pub fn from_game_start(game: &GameEventInfo) -> Self {
         let clock_settings = game
             .seconds_left
-            .map(|seconds| ClockSettings { limit: (seconds / 60) as u32, increment: 0 });
+            .map(|seconds| ClockSettings { initial_seconds: seconds as u32, increment: 0 });
 
         let timer = Timer::new(game.seconds_left.unwrap_or_default() * 1000);
         let us = Player {
@@ -320,29 +359,53 @@ impl Game {
             None
         };
 
+        // The moves so far aren't known yet, so replays start from here until the full game
+        // arrives. Standard games are assumed to have started from the usual position.
+        let variant = game.variant.key.clone();
+        let is_chess960 = matches!(variant, VariantKey::Chess960);
+        let position = Position::from_fen(&game.fen, is_chess960).unwrap_or_default();
+        let (start_position, initial_fen) = match variant {
+            VariantKey::Standard => (Position::default(), None),
+            _ => (position.clone(), game.fen.clone().into()),
+        };
+
         Self {
             game_id: game.game_id.clone(),
             speed: game.speed.clone(),
+            variant,
+            rated: game.rated,
             timestamp: Instant::now(),
             clock_settings,
-            board: chess::Board::from_str(&game.fen).unwrap(),
+            position,
+            start_position,
+            initial_fen,
             move_history: Default::default(),
             last_move,
+            move_clocks: Default::default(),
             is_our_turn: game.is_my_turn,
             us,
             opponent,
             finished: false,
             timers_started: false,
+            started_at: SystemTime::now(),
+            status: "started".to_string(),
+            winner: None,
         }
     }
 
-    pub fn from_game_full(our_id: &str, game: &GameFull) -> Self {
-        let mut board = board_from_api_fen(game.initial_fen.clone());
+    pub fn from_game_full(our_id: &str, game: &GameFull, received_at: Instant) -> Self {
+        let variant = game.variant.key.clone();
+        let start_position = position_from_api_fen(game.initial_fen.clone(), &variant);
+        let initial_fen = game.initial_fen.clone().filter(|fen| fen != "startpos");
+        let mut position = start_position.clone();
 
         let our_name = "Twitch".to_string();
         let our_color = color_from_game(game, &our_id).unwrap();
 
-        let mut move_history = Default::default();
+        let mut move_history: Vec<String> = Default::default();
+        let mut move_clocks: Vec<Option<u64>> = Default::default();
+        let mut status = "started".to_string();
+        let mut winner = None;
 
         let timer_millis = game.clock.clone().map(|c| c.initial).unwrap_or_default() as u64;
         let timer = Timer::new(timer_millis);
@@ -381,63 +444,82 @@ impl Game {
 
         if let Some(game_state) = &game.state {
             if our_color == chess::Color::Black {
-                us.timer = Timer::new(game_state.btime);
-                opponent.timer = Timer::new(game_state.wtime);
+                us.timer = Timer::reported_at(game_state.btime, received_at);
+                opponent.timer = Timer::reported_at(game_state.wtime, received_at);
             } else {
-                us.timer = Timer::new(game_state.wtime);
-                opponent.timer = Timer::new(game_state.btime);
+                us.timer = Timer::reported_at(game_state.wtime, received_at);
+                opponent.timer = Timer::reported_at(game_state.btime, received_at);
             }
 
             let moves: Vec<&str> = game_state.moves.split(" ").collect();
 
             move_history = moves.iter().map(|m| m.to_string()).collect();
+            move_clocks = clocks_after(&start_position, &[], &move_history, game_state);
             last_move = moves.last().and_then(|m| chess::ChessMove::from_str(m).ok());
+            status = game_state.status.to_string();
+            winner = game_state.winner.as_deref().and_then(color_from_name);
 
-            if let Some(new_board) = board_from_moves(moves.clone()) {
-                board = new_board;
+            if let Some(new_position) = board_from_moves(&start_position, moves.clone()) {
+                position = new_position;
             }
         }
 
-        let clock_settings = game
-            .clock
-            .clone()
-            .map(|c| ClockSettings { limit: c.initial / 60000, increment: c.increment / 1000 });
+        let clock_settings = game.clock.clone().map(|c| ClockSettings {
+            initial_seconds: c.initial / 1000,
+            increment: c.increment / 1000,
+        });
 
-        let is_our_turn = our_color == board.side_to_move();
+        let is_our_turn = our_color == position.board.side_to_move();
+        let timers_started = move_clocks.len() >= 2 && status == "started";
 
-        Self {
+        let mut game = Self {
             game_id: game.id.to_string(),
             speed: game.speed.clone(),
+            variant,
+            rated: game.rated,
             timestamp: Instant::now(),
             clock_settings,
-            board,
+            position,
+            start_position,
+            initial_fen,
             move_history,
+            move_clocks,
             last_move,
             is_our_turn,
             us,
             opponent,
             finished: false,
-            timers_started: false,
-        }
+            timers_started,
+            started_at: SystemTime::now(),
+            status,
+            winner,
+        };
+
+        game.run_clocks();
+        game
     }
 
-    pub fn elapse_time(&mut self, milliseconds: u64) {
-        if self.finished || !self.timers_started {
-            return;
-        }
+    pub fn is_correspondence(&self) -> bool {
+        matches!(self.speed, Speed::Correspondence)
+    }
 
-        if self.is_our_turn {
-            self.us.timer.elapse(milliseconds);
-        } else {
-            self.opponent.timer.elapse(milliseconds);
-        }
+    /// Moves played so far. Lichess sends no moves as a single empty one.
+    pub fn move_count(&self) -> usize {
+        self.move_history.iter().filter(|m| !m.is_empty()).count()
+    }
+
+    /// Runs the clock of whoever is to move, once both sides have moved and until the end.
+    fn run_clocks(&mut self) {
+        let running = self.timers_started && !self.finished;
+        self.us.timer.set_running(running && self.is_our_turn);
+        self.opponent.timer.set_running(running && !self.is_our_turn);
     }
 
     pub fn process_game_info(&mut self, game: &GameEventInfo) {
         if self.clock_settings.is_none() {
             self.clock_settings = game
                 .seconds_left
-                .map(|seconds| ClockSettings { limit: (seconds / 60) as u32, increment: 0 });
+                .map(|seconds| ClockSettings { initial_seconds: seconds as u32, increment: 0 });
         }
 
         self.opponent.name =
@@ -448,30 +530,35 @@ impl Game {
         self.is_our_turn = game.is_my_turn;
     }
 
-    pub fn process_game_state(&mut self, game: &GameState) {
+    pub fn process_game_state(&mut self, game: &GameState, received_at: Instant) {
         let moves: Vec<&str> = game.moves.split(" ").collect();
-        self.move_history = moves.iter().map(|m| m.to_string()).collect();
+        let move_history: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        self.move_clocks =
+            clocks_after(&self.start_position, &self.move_clocks, &move_history, game);
+        self.move_history = move_history;
+        self.status = game.status.to_string();
+        self.winner = game.winner.as_deref().and_then(color_from_name);
         self.last_move = moves.last().map(|m| chess::ChessMove::from_str(m).ok()).flatten();
         self.timers_started = self.move_history.len() >= 2;
 
-        let Some(board) = board_from_moves(moves) else {
+        let Some(position) = board_from_moves(&self.start_position, moves) else {
             log::warn!("Board status for game {} is no longer ongoing", self.game_id);
             return;
         };
 
-        self.board = board;
-        self.is_our_turn = self.us.color == board.side_to_move();
+        self.is_our_turn = self.us.color == position.board.side_to_move();
+        self.position = position;
 
         if self.us.color == chess::Color::Black {
-            self.us.timer = Timer::new(game.btime);
-            self.opponent.timer = Timer::new(game.wtime);
+            self.us.timer = Timer::reported_at(game.btime, received_at);
+            self.opponent.timer = Timer::reported_at(game.wtime, received_at);
         } else {
-            self.us.timer = Timer::new(game.wtime);
-            self.opponent.timer = Timer::new(game.btime);
+            self.us.timer = Timer::reported_at(game.wtime, received_at);
+            self.opponent.timer = Timer::reported_at(game.btime, received_at);
         }
 
         if let Some(clock_settings) = &mut self.clock_settings {
-            clock_settings.increment = (game.binc / 10000) as u32;
+            clock_settings.increment = (game.binc / 1000) as u32;
         }
 
         // TODO: Refactor this as an enum in the lichess api crate.
@@ -479,40 +566,100 @@ impl Game {
             log::info!("Game {} finished", self.game_id);
             self.finished = true;
         }
+
+        self.run_clocks();
+    }
+}
+
+impl GameOutcome {
+    /// None for games that never really happened, e.g. aborted ones.
+    pub fn from_game_state(our_color: chess::Color, game_state: &GameState) -> Option<Self> {
+        if game_state.status == "aborted" || game_state.status == "noStart" {
+            return None;
+        }
+
+        let outcome = match &game_state.winner {
+            Some(winner) if *winner == color_name(our_color) => GameOutcome::Win,
+            Some(_) => GameOutcome::Loss,
+            None => GameOutcome::Draw,
+        };
+
+        outcome.into()
+    }
+
+    pub fn score(&self) -> f32 {
+        match self {
+            GameOutcome::Win => 1.0,
+            GameOutcome::Loss => 0.0,
+            GameOutcome::Draw => 0.5,
+        }
     }
 }
 
-fn board_from_moves(moves: Vec<&str>) -> Option<chess::Board> {
-    let mut board = chess::Board::default();
-    let mut result = chess::Board::default();
+fn color_name(color: chess::Color) -> &'static str {
+    match color {
+        chess::Color::White => "white",
+        chess::Color::Black => "black",
+    }
+}
+
+fn color_from_name(name: &str) -> Option<chess::Color> {
+    match name {
+        "white" => chess::Color::White.into(),
+        "black" => chess::Color::Black.into(),
+        _ => None,
+    }
+}
+
+/// Keeps the clocks already known and adds the mover's clock for the latest move. Moves
+/// that arrived together, e.g. while reconnecting, have no clock.
+fn clocks_after(
+    start_position: &Position,
+    clocks: &[Option<u64>],
+    move_history: &[String],
+    game_state: &GameState,
+) -> Vec<Option<u64>> {
+    let move_count = move_history.iter().filter(|m| !m.is_empty()).count();
+    let mut clocks: Vec<Option<u64>> = clocks.iter().copied().take(move_count).collect();
+    if move_count <= clocks.len() {
+        return clocks;
+    }
+
+    clocks.resize(move_count, None);
+    let first_mover = start_position.board.side_to_move();
+    let mover = if move_count % 2 == 1 { first_mover } else { !first_mover };
+    clocks[move_count - 1] = match mover {
+        chess::Color::White => game_state.wtime,
+        chess::Color::Black => game_state.btime,
+    }
+    .into();
+
+    clocks
+}
+
+fn board_from_moves(start_position: &Position, moves: Vec<&str>) -> Option<Position> {
+    let mut position = start_position.clone();
 
     for game_move in moves {
         if game_move.is_empty() {
             continue;
         }
-        if let BoardStatus::Ongoing = board.status() {
-            if let Some(chess_move) = chess::ChessMove::from_str(game_move).ok() {
-                board.make_move(chess_move, &mut result);
-                board = result;
+        if let BoardStatus::Ongoing = position.status() {
+            let chess_move = chess::ChessMove::from_str(game_move).ok();
+            if let Some(next) = chess_move.and_then(|chess_move| position.make_move(chess_move)) {
+                position = next;
             }
         } else {
             return None;
         }
     }
 
-    board.into()
+    position.into()
 }
 
-fn board_from_api_fen(fen: Option<String>) -> chess::Board {
-    if let Some(fen) = fen {
-        if fen == "startpos" {
-            chess::Board::default()
-        } else {
-            chess::Board::from_str(&fen).unwrap_or_default()
-        }
-    } else {
-        chess::Board::default()
-    }
+fn position_from_api_fen(fen: Option<String>, variant: &VariantKey) -> Position {
+    let is_chess960 = matches!(variant, VariantKey::Chess960);
+    fen.and_then(|fen| Position::from_fen(&fen, is_chess960)).unwrap_or_default()
 }
 
 fn color_from_api_color(color: &Color) -> Option<chess::Color> {
@@ -2,11 +2,20 @@ pub mod action;
 pub mod challenge;
 pub mod events;
 pub mod game;
+pub mod position;
+pub mod profile;
+pub mod tv;
 
 use lichess_api::client::LichessApi;
 
+use crate::config::AccountMode;
+
 #[derive(Clone)]
 pub struct Context {
     pub our_id: String,
+    pub mode: AccountMode,
     pub api: LichessApi<reqwest::Client>,
+    /// For the few requests the API crate can't make, e.g. challenges with a colour.
+    pub client: reqwest::Client,
+    pub access_token: String,
 }
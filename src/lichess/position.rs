@@ -0,0 +1,393 @@
+use std::str::FromStr;
+
+use chess::{Board, BoardBuilder, BoardStatus, ChessMove, Color, File, Piece, Rank, Square};
+
+/// A board along with the Chess960 castling rights the `chess` crate can't track.
+///
+/// The crate only knows castling with the king on e1 and rooks in the corners, so in a
+/// Chess960 game its castling rights are cleared and castling is handled here instead.
+/// Castling is written the way Lichess does, as the king moving onto its own rook.
+#[derive(Clone)]
+pub struct Position {
+    pub board: Board,
+    /// Rooks that can still castle in a Chess960 game, or None for standard castling.
+    castling_rooks: Option<Vec<Square>>,
+}
+
+struct Castle {
+    king_from: Square,
+    king_to: Square,
+    rook_from: Square,
+    rook_to: Square,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { board: Board::default(), castling_rooks: None }
+    }
+}
+
+impl Position {
+    /// A position with standard castling.
+    pub fn from_board(board: Board) -> Self {
+        Self { board, castling_rooks: None }
+    }
+
+    /// Accepts "startpos" along with Shredder-FEN and X-FEN castling rights for Chess960.
+    pub fn from_fen(fen: &str, chess960: bool) -> Option<Self> {
+        if fen == "startpos" {
+            return Self::default().into();
+        }
+
+        if !chess960 {
+            let board = Board::from_str(fen).ok()?;
+            return Self { board, castling_rooks: None }.into();
+        }
+
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        let castling = *fields.get(2)?;
+        fields[2] = "-";
+        let board = Board::from_str(&fields.join(" ")).ok()?;
+
+        let castling_rooks = castling
+            .chars()
+            .filter(|c| *c != '-')
+            .filter_map(|c| castling_rook(&board, c))
+            .collect();
+
+        Self { board, castling_rooks: Some(castling_rooks) }.into()
+    }
+
+    pub fn status(&self) -> BoardStatus {
+        match self.board.status() {
+            BoardStatus::Stalemate if !self.castles().is_empty() => BoardStatus::Ongoing,
+            status => status,
+        }
+    }
+
+    pub fn is_legal(&self, chess_move: ChessMove) -> bool {
+        match self.castle(chess_move) {
+            Some(castle) => self.castled(&castle).is_some(),
+            None => self.board.legal(chess_move),
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        let mut moves: Vec<ChessMove> = chess::MoveGen::new_legal(&self.board).collect();
+        moves.extend(self.castles());
+        moves
+    }
+
+    /// The position after the move, or None if it isn't legal.
+    pub fn make_move(&self, chess_move: ChessMove) -> Option<Self> {
+        if let Some(castle) = self.castle(chess_move) {
+            let board = self.castled(&castle)?;
+            let rank = castle.king_from.get_rank();
+            let castling_rooks = self.rooks_without(|rook| rook.get_rank() != rank);
+            return Self { board, castling_rooks }.into();
+        }
+
+        if !self.board.legal(chess_move) {
+            return None;
+        }
+
+        let (source, dest) = (chess_move.get_source(), chess_move.get_dest());
+        let is_king = self.board.piece_on(source) == Some(Piece::King);
+        let castling_rooks = self.rooks_without(|rook| {
+            let moved_off = is_king && rook.get_rank() == source.get_rank();
+            !moved_off && *rook != source && *rook != dest
+        });
+
+        Self { board: self.board.make_move_new(chess_move), castling_rooks }.into()
+    }
+
+    /// The move in Standard Algebraic Notation, e.g. "Nbd7", "exd6", "O-O" or "e8=Q#".
+    pub fn san(&self, chess_move: ChessMove) -> Option<String> {
+        let next = self.make_move(chess_move)?;
+        let (source, dest) = (chess_move.get_source(), chess_move.get_dest());
+        let piece = self.board.piece_on(source)?;
+
+        let king_to = match self.castle(chess_move) {
+            Some(castle) => castle.king_to.into(),
+            // Standard castling is written as the king moving two squares.
+            None if piece == Piece::King
+                && source.get_file().to_index().abs_diff(dest.get_file().to_index()) == 2 =>
+            {
+                dest.into()
+            }
+            None => None,
+        };
+
+        let mut san = match king_to {
+            Some(king_to) if king_to.get_file() == File::G => "O-O".to_string(),
+            Some(_) => "O-O-O".to_string(),
+            None if piece == Piece::Pawn => {
+                let mut san = String::new();
+                if source.get_file() != dest.get_file() {
+                    san.push_str(&format!("{}x", file_name(source.get_file())));
+                }
+                san.push_str(&dest.to_string());
+                if let Some(promotion) = chess_move.get_promotion() {
+                    san.push_str(&format!("={}", piece_letter(promotion)));
+                }
+                san
+            }
+            None => {
+                let mut san = piece_letter(piece).to_string();
+                san.push_str(&self.disambiguation(piece, source, dest));
+                if self.board.piece_on(dest).is_some() {
+                    san.push('x');
+                }
+                san.push_str(&dest.to_string());
+                san
+            }
+        };
+
+        if next.status() == BoardStatus::Checkmate {
+            san.push('#');
+        } else if next.board.checkers().popcnt() != 0 {
+            san.push('+');
+        }
+
+        san.into()
+    }
+
+    /// The file, rank or both of the source, if another piece of the same kind could also
+    /// move to the destination.
+    fn disambiguation(&self, piece: Piece, source: Square, dest: Square) -> String {
+        let others: Vec<Square> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| other.get_dest() == dest && other.get_source() != source)
+            .filter(|other| self.board.piece_on(other.get_source()) == Some(piece))
+            .map(|other| other.get_source())
+            .collect();
+
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| other.get_file() != source.get_file()) {
+            file_name(source.get_file()).to_string()
+        } else if others.iter().all(|other| other.get_rank() != source.get_rank()) {
+            (source.get_rank().to_index() + 1).to_string()
+        } else {
+            source.to_string()
+        }
+    }
+
+    fn rooks_without(&self, keep: impl Fn(&Square) -> bool) -> Option<Vec<Square>> {
+        let rooks = self.castling_rooks.as_ref()?;
+        rooks.iter().copied().filter(|rook| keep(rook)).collect::<Vec<Square>>().into()
+    }
+
+    fn castles(&self) -> Vec<ChessMove> {
+        let Some(rooks) = &self.castling_rooks else {
+            return Default::default();
+        };
+
+        let color = self.board.side_to_move();
+        let king = self.board.king_square(color);
+
+        rooks
+            .iter()
+            .filter(|rook| self.board.color_on(**rook) == Some(color))
+            .map(|rook| ChessMove::new(king, *rook, None))
+            .filter(|chess_move| self.is_legal(*chess_move))
+            .collect()
+    }
+
+    /// Where the king and rook go, if the move is the king moving onto a castling rook.
+    fn castle(&self, chess_move: ChessMove) -> Option<Castle> {
+        let rooks = self.castling_rooks.as_ref()?;
+        let (king_from, rook_from) = (chess_move.get_source(), chess_move.get_dest());
+        let color = self.board.side_to_move();
+
+        let is_king = self.board.piece_on(king_from) == Some(Piece::King);
+        let own_king = self.board.color_on(king_from) == Some(color);
+        let own_rook = self.board.color_on(rook_from) == Some(color);
+        if !is_king || !own_king || !own_rook || !rooks.contains(&rook_from) {
+            return None;
+        }
+
+        let rank = color.to_my_backrank();
+        let (king_file, rook_file) =
+            if rook_from.get_file().to_index() > king_from.get_file().to_index() {
+                (File::G, File::F)
+            } else {
+                (File::C, File::D)
+            };
+
+        Castle {
+            king_from,
+            king_to: Square::make_square(rank, king_file),
+            rook_from,
+            rook_to: Square::make_square(rank, rook_file),
+        }
+        .into()
+    }
+
+    /// The board after castling, or None if something's in the way or the king is attacked.
+    fn castled(&self, castle: &Castle) -> Option<Board> {
+        let color = self.board.side_to_move();
+        if self.board.checkers().popcnt() != 0 {
+            return None;
+        }
+
+        let rank = castle.king_from.get_rank();
+        let squares = [castle.king_from, castle.king_to, castle.rook_from, castle.rook_to];
+        let low = squares.iter().map(|square| square.get_file().to_index()).min()?;
+        let high = squares.iter().map(|square| square.get_file().to_index()).max()?;
+
+        for file in low..=high {
+            let square = Square::make_square(rank, File::from_index(file));
+            let is_castling_piece = square == castle.king_from || square == castle.rook_from;
+            if !is_castling_piece && self.board.piece_on(square).is_some() {
+                return None;
+            }
+        }
+
+        // The king can't pass through an attacked square on its way.
+        for square in between(castle.king_from, castle.king_to, rank) {
+            let mut builder = BoardBuilder::from(&self.board);
+            builder.clear_square(castle.king_from).piece(square, Piece::King, color);
+            let board = Board::try_from(&builder).ok()?;
+            if board.checkers().popcnt() != 0 {
+                return None;
+            }
+        }
+
+        let mut builder = BoardBuilder::from(&self.board);
+        builder
+            .clear_square(castle.king_from)
+            .clear_square(castle.rook_from)
+            .piece(castle.king_to, Piece::King, color)
+            .piece(castle.rook_to, Piece::Rook, color)
+            .side_to_move(!color)
+            .en_passant(None);
+
+        // Fails if the king would be left in check.
+        Board::try_from(&builder).ok()
+    }
+}
+
+fn file_name(file: File) -> char {
+    (b'a' + file.to_index() as u8) as char
+}
+
+fn piece_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+/// The squares strictly between two squares on a rank.
+fn between(from: Square, to: Square, rank: Rank) -> Vec<Square> {
+    let (from, to) = (from.get_file().to_index(), to.get_file().to_index());
+    let (low, high) = (from.min(to), from.max(to));
+
+    (low + 1..high).map(|file| Square::make_square(rank, File::from_index(file))).collect()
+}
+
+/// K and Q pick the outermost rook on that side of the king, as in X-FEN.
+fn castling_rook(board: &Board, right: char) -> Option<Square> {
+    let color = if right.is_ascii_uppercase() { Color::White } else { Color::Black };
+    let rank = color.to_my_backrank();
+    let king_file = board.king_square(color).get_file().to_index();
+
+    let is_rook = |file: usize| {
+        let square = Square::make_square(rank, File::from_index(file));
+        let is_ours = board.color_on(square) == Some(color);
+        (is_ours && board.piece_on(square) == Some(Piece::Rook)).then_some(square)
+    };
+
+    match right.to_ascii_lowercase() {
+        'k' => (king_file + 1..8).rev().find_map(is_rook),
+        'q' => (0..king_file).find_map(is_rook),
+        file @ 'a'..='h' => is_rook(file as usize - 'a' as usize),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str, chess960: bool) -> Position {
+        Position::from_fen(fen, chess960).unwrap()
+    }
+
+    fn chess_move(uci: &str) -> ChessMove {
+        ChessMove::from_str(uci).unwrap()
+    }
+
+    fn san(position: &Position, uci: &str) -> String {
+        position.san(chess_move(uci)).unwrap()
+    }
+
+    #[test]
+    fn san_of_simple_moves() {
+        let position = Position::default();
+        assert_eq!(san(&position, "e2e4"), "e4");
+        assert_eq!(san(&position, "g1f3"), "Nf3");
+    }
+
+    #[test]
+    fn san_disambiguates_by_file_then_rank() {
+        let knights = position("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", false);
+        assert_eq!(san(&knights, "b1d2"), "Nbd2");
+
+        let rooks = position("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", false);
+        assert_eq!(san(&rooks, "a1a3"), "R1a3");
+    }
+
+    #[test]
+    fn san_of_en_passant_and_promotion() {
+        let en_passant = position("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", false);
+        assert_eq!(san(&en_passant, "e5d6"), "exd6");
+
+        let promotion = position("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", false);
+        assert_eq!(san(&promotion, "e7e8q"), "e8=Q+");
+    }
+
+    #[test]
+    fn san_marks_checkmate() {
+        let position = position("7k/8/6K1/8/8/8/8/R7 w - - 0 1", false);
+        assert_eq!(san(&position, "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn san_of_standard_castling() {
+        let position = position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", false);
+        assert_eq!(san(&position, "e1g1"), "O-O");
+        assert_eq!(san(&position, "e1c1"), "O-O-O");
+    }
+
+    #[test]
+    fn chess960_castling_with_the_rook_on_the_kings_destination() {
+        let kingside = position("4k3/8/8/8/8/8/8/5KR1 w G - 0 1", true);
+        assert_eq!(san(&kingside, "f1g1"), "O-O");
+        let castled = kingside.make_move(chess_move("f1g1")).unwrap();
+        assert_eq!(castled.board.piece_on(Square::G1), Some(Piece::King));
+        assert_eq!(castled.board.piece_on(Square::F1), Some(Piece::Rook));
+
+        let queenside = position("4k3/8/8/8/8/8/8/2RK4 w C - 0 1", true);
+        assert_eq!(san(&queenside, "d1c1"), "O-O-O");
+        let castled = queenside.make_move(chess_move("d1c1")).unwrap();
+        assert_eq!(castled.board.piece_on(Square::C1), Some(Piece::King));
+        assert_eq!(castled.board.piece_on(Square::D1), Some(Piece::Rook));
+    }
+
+    #[test]
+    fn chess960_castling_rights_are_lost_once_the_king_moves() {
+        let position = position("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", true);
+        assert_eq!(position.castles().len(), 2);
+
+        let moved = position.make_move(chess_move("e1e2")).unwrap();
+        let back = moved.make_move(chess_move("e8d8")).unwrap();
+        assert!(back.castles().is_empty());
+    }
+}
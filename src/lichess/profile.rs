@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+/// The parts of a user's public data shown on stream. Read straight from `/api/user`, as it
+/// includes their bio, join date and game count.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicProfile {
+    pub id: String,
+    pub title: Option<String>,
+    /// Keyed by perf, e.g. "blitz" or "chess960". Puzzle modes like "storm" have no rating.
+    #[serde(default)]
+    pub perfs: HashMap<String, PerfSummary>,
+    pub profile: Option<ProfileDetails>,
+    /// Milliseconds since the epoch.
+    pub created_at: Option<u64>,
+    pub count: Option<GameCount>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PerfSummary {
+    pub rating: Option<u32>,
+    #[serde(default)]
+    pub games: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProfileDetails {
+    pub bio: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GameCount {
+    pub all: u32,
+}
+
+impl PublicProfile {
+    pub fn created_at(&self) -> Option<SystemTime> {
+        let created_at = self.created_at?;
+        UNIX_EPOCH.checked_add(Duration::from_millis(created_at))
+    }
+
+    /// The first non-empty line of their bio.
+    pub fn bio_line(&self) -> Option<String> {
+        let bio = self.profile.as_ref()?.bio.as_ref()?;
+        bio.lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string)
+    }
+}
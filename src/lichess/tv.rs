@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::engine::events::internal::{EventSender, Notification};
+use crate::lichess::Context;
+use crate::stream::model::{Player, SpectatedGame, Timer};
+
+/// How long to wait before reconnecting to the live feed after it drops.
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// The featured game on Lichess TV as of its latest move.
+#[derive(Clone, Debug)]
+pub struct TvGame {
+    /// The side Lichess TV shows at the bottom.
+    pub orientation: chess::Color,
+    pub white: TvPlayer,
+    pub black: TvPlayer,
+    pub board: chess::Board,
+}
+
+#[derive(Clone, Debug)]
+pub struct TvPlayer {
+    pub name: String,
+    pub rating: Option<u32>,
+    pub seconds: u64,
+}
+
+/// The feed's messages, one per line. Each game starts with "featured", then a "fen" follows
+/// every move.
+#[derive(Deserialize)]
+#[serde(tag = "t", content = "d", rename_all = "camelCase")]
+enum FeedMessage {
+    Featured(Featured),
+    Fen(FenUpdate),
+}
+
+#[derive(Deserialize)]
+struct Featured {
+    orientation: String,
+    players: Vec<FeedPlayer>,
+    fen: String,
+}
+
+#[derive(Deserialize)]
+struct FeedPlayer {
+    color: String,
+    user: Option<FeedUser>,
+    rating: Option<u32>,
+    seconds: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct FeedUser {
+    name: String,
+    title: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FenUpdate {
+    fen: String,
+    wc: Option<u64>,
+    bc: Option<u64>,
+}
+
+/// Follows Lichess TV, sending a TvUpdate after every move. A recorded feed - the live one
+/// saved to a file - is replayed on a loop instead if given, for testing offline.
+pub fn follow(
+    context: &Context,
+    recording: Option<PathBuf>,
+    replay_interval: Duration,
+    event_sender: EventSender,
+) {
+    let client = context.client.clone();
+    let mut reader = FeedReader { game: None, event_sender };
+
+    tokio::task::spawn(async move {
+        match recording {
+            Some(path) => reader.replay(path, replay_interval).await,
+            None => loop {
+                if let Err(error) = reader.stream(&client).await {
+                    log::warn!("[TV] Feed dropped: {}", error);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            },
+        }
+    });
+}
+
+struct FeedReader {
+    game: Option<TvGame>,
+    event_sender: EventSender,
+}
+
+impl FeedReader {
+    async fn stream(&mut self, client: &reqwest::Client) -> reqwest::Result<()> {
+        let mut response =
+            client.get("https://lichess.org/api/tv/feed").send().await?.error_for_status()?;
+
+        let mut buffer = Vec::<u8>::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                self.read_line(&line);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn replay(&mut self, path: PathBuf, replay_interval: Duration) {
+        let recording = match std::fs::read(&path) {
+            Ok(recording) => recording,
+            Err(error) => {
+                log::error!("[TV] Failed to read recorded feed {:?}: {}", &path, error);
+                return;
+            }
+        };
+
+        loop {
+            for line in recording.split(|byte| *byte == b'\n') {
+                if self.read_line(line) {
+                    tokio::time::sleep(replay_interval).await;
+                }
+            }
+        }
+    }
+
+    /// Returns true if the line moved the game on.
+    fn read_line(&mut self, line: &[u8]) -> bool {
+        // Empty lines are keep-alives.
+        if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+            return false;
+        }
+
+        let message = match serde_json::from_slice::<FeedMessage>(line) {
+            Ok(message) => message,
+            Err(error) => {
+                log::debug!("[TV] Skipping feed line: {}", error);
+                return false;
+            }
+        };
+
+        let game = match message {
+            FeedMessage::Featured(featured) => TvGame::from_featured(featured),
+            FeedMessage::Fen(update) => self.game.clone().and_then(|game| game.updated(update)),
+        };
+        let Some(game) = game else {
+            return false;
+        };
+
+        self.game = game.clone().into();
+        self.event_sender.send_notification(Notification::TvUpdate { game });
+        true
+    }
+}
+
+impl TvGame {
+    fn from_featured(featured: Featured) -> Option<Self> {
+        let player = |color: &str| {
+            let player = featured.players.iter().find(|player| player.color == color)?;
+            let name = match &player.user {
+                Some(FeedUser { name, title: Some(title) }) => format!("{} {}", title, name),
+                Some(FeedUser { name, title: None }) => name.to_string(),
+                None => "Anonymous".to_string(),
+            };
+            let seconds = player.seconds.unwrap_or_default();
+            TvPlayer { name, rating: player.rating, seconds }.into()
+        };
+
+        let orientation = match featured.orientation.as_str() {
+            "black" => chess::Color::Black,
+            _ => chess::Color::White,
+        };
+
+        Self {
+            orientation,
+            white: player("white")?,
+            black: player("black")?,
+            board: board_from_feed_fen(&featured.fen)?,
+        }
+        .into()
+    }
+
+    fn updated(mut self, update: FenUpdate) -> Option<Self> {
+        self.board = board_from_feed_fen(&update.fen)?;
+        self.white.seconds = update.wc.unwrap_or(self.white.seconds);
+        self.black.seconds = update.bc.unwrap_or(self.black.seconds);
+        self.into()
+    }
+
+    /// As drawn on stream, with the clock of the side to move running.
+    pub fn view(&self) -> SpectatedGame {
+        let side_to_move = self.board.side_to_move();
+        let player = |player: &TvPlayer, color: chess::Color| {
+            let mut timer = Timer::new(player.seconds * 1000);
+            timer.set_running(color == side_to_move);
+            Player { name: player.name.to_string(), color, rating: player.rating, timer }
+        };
+
+        SpectatedGame {
+            white: player(&self.white, chess::Color::White),
+            black: player(&self.black, chess::Color::Black),
+            orientation: self.orientation,
+            board: self.board,
+        }
+    }
+}
+
+/// The feed's FENs may stop after the side to move, which is all that's needed to draw them.
+fn board_from_feed_fen(fen: &str) -> Option<chess::Board> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next()?;
+    let side_to_move = fields.next().unwrap_or("w");
+
+    chess::Board::from_str(&format!("{} {} - - 0 1", placement, side_to_move)).ok()
+}
@@ -80,17 +80,17 @@ pub fn run_stream_manager(
 pub fn make_engine(stream_events: EventSender, config: Config) -> Engine {
     let lichess_context = make_lichess_context(&config.lichess);
     let twitch_context = make_twitch_context(&config.twitch);
-    Engine::new(stream_events, lichess_context, twitch_context)
+    Engine::new(stream_events, lichess_context, twitch_context, config)
 }
 
 pub fn make_lichess_context(config: &config::Lichess) -> LichessContext {
     let our_id = config.account.to_string();
 
     let client = reqwest::Client::builder().build().unwrap();
-    let access_token = config.access_token.to_string().into();
-    let api = lichess_api::client::LichessApi::new(client, access_token);
+    let access_token = config.access_token.to_string();
+    let api = lichess_api::client::LichessApi::new(client.clone(), access_token.clone().into());
 
-    LichessContext { our_id, api }
+    LichessContext { our_id, mode: config.mode, api, client, access_token }
 }
 
 pub fn make_twitch_context(config: &config::Twitch) -> TwitchContext {
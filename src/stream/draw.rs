@@ -12,7 +12,11 @@ use crate::engine::votes::settings::Settings;
 
 use super::font::Fonts;
 use super::image::Images;
-use super::model::{Command, GameVotes, Model, Notice, Player, State, Title};
+use super::model::{
+    Command, CorrespondencePanel, GameChatLine, GameVotes, HeadToHead, LobbyView, Model, Notice,
+    OpponentCard, Player, RematchView, SessionRating, SimulPanel, SpectatedGame, State, Title,
+    VoteStats,
+};
 
 pub const FRAME_DIMS_U32: (u32, u32) = (1920, 1080);
 pub const FRAME_DIMS_F32: (f32, f32) = (1920.0, 1080.0);
@@ -50,6 +54,27 @@ const SQUARE_DIMS: (f32, f32) = (BOARD_DIMS.0 / 8.0, BOARD_DIMS.1 / 8.0);
 const USER_ORIGIN: (f32, f32) = (TITLE_ORIGIN.0, BOARD_ORIGIN.1 + BOARD_DIMS.1);
 const _USER_DIMS: (f32, f32) = PLAYER_DIMS;
 
+// Simul grid - replaces the player bars and board with a 2x2 grid of smaller boards.
+
+const SIMUL_ORIGIN: (f32, f32) = OPPONENT_ORIGIN;
+const SIMUL_PANEL_DIMS: (f32, f32) = (TITLE_DIMS.0 / 2.0, (FRAME_DIMS_F32.1 - TITLE_DIMS.1) / 2.0);
+const SIMUL_BOARD_OFFSET: (f32, f32) = (20.0, 44.0);
+const SIMUL_SQUARE_DIMS: (f32, f32) = (37.5, 37.5);
+
+// Lobby - replaces the player bars and board between games while chat picks an opponent.
+
+const LOBBY_ORIGIN: (f32, f32) = OPPONENT_ORIGIN;
+const LOBBY_DIMS: (f32, f32) = (TITLE_DIMS.0, FRAME_DIMS_F32.1 - TITLE_DIMS.1);
+const LOBBY_ROW_HEIGHT: f32 = 140.0;
+
+// Rematch - drawn over the board after a game while chat votes to play again.
+
+const REMATCH_DIMS: (f32, f32) = (560.0, 220.0);
+const REMATCH_ORIGIN: (f32, f32) = (
+    BOARD_ORIGIN.0 + (BOARD_DIMS.0 - REMATCH_DIMS.0) / 2.0,
+    BOARD_ORIGIN.1 + (BOARD_DIMS.1 - REMATCH_DIMS.1) / 2.0,
+);
+
 // Right column.
 
 const GAME_VOTES_ORIGIN: (f32, f32) = (TITLE_ORIGIN.0 + TITLE_DIMS.0, 0.0);
@@ -58,6 +83,15 @@ const GAME_VOTES_DIMS: (f32, f32) = (620.0, FRAME_DIMS_F32.1 / 2.0);
 const COMMANDS_ORIGIN: (f32, f32) = (GAME_VOTES_ORIGIN.0, GAME_VOTES_ORIGIN.1 + GAME_VOTES_DIMS.1);
 const COMMANDS_DIMS: (f32, f32) = (1200.0, FRAME_DIMS_F32.1 / 2.0);
 
+const SPARKLINE_DIMS: (f32, f32) = (150.0, 26.0);
+const RATING_GRAPH_DIMS: (f32, f32) = (180.0, 60.0);
+
+/// Rows of text that fit in the chat commands box below its header.
+const COMMANDS_MAX_ROWS: usize = 14;
+const CORRESPONDENCE_MAX_ROWS: usize = 5;
+const GAME_CHAT_MAX_ROWS: usize = 4;
+const OPPONENT_MAX_ROWS: usize = 4;
+
 // Draw properties.
 
 const BORDER_STROKE_WIDTH: f32 = 4.0;
@@ -76,6 +110,7 @@ struct Sources {
 
 struct StrokeStyles {
     border: StrokeStyle,
+    sparkline: StrokeStyle,
 }
 
 impl Context {
@@ -89,6 +124,12 @@ impl Context {
             dash_array: vec![],
             dash_offset: 0.0,
         };
+        let sparkline_stroke = StrokeStyle {
+            width: 3.0,
+            cap: LineCap::Round,
+            join: LineJoin::Round,
+            ..Default::default()
+        };
         Self {
             target: DrawTarget::new(width, height),
             sources: Sources {
@@ -96,7 +137,7 @@ impl Context {
                 black: SolidSource::from_unpremultiplied_argb(0xff, 0, 0, 0),
                 white: SolidSource::from_unpremultiplied_argb(0xff, 0xff, 0xff, 0xff),
             },
-            strokes: StrokeStyles { border: border_stroke },
+            strokes: StrokeStyles { border: border_stroke, sparkline: sparkline_stroke },
         }
     }
 
@@ -112,18 +153,46 @@ impl Context {
 
     fn draw_elements(&mut self, model: &Model, images: &Images, fonts: &Fonts) {
         self.draw_notice(&model.notice, &fonts);
-        self.draw_current_state(&model.state, &fonts);
+        self.draw_current_state(&model.state, &model.challenge_queue, &fonts);
         self.draw_settings(&model.settings, &fonts);
         self.draw_move_history(&model.move_history, &fonts);
 
         self.draw_title(&model.title, &fonts);
-        self.draw_opponent_bar(&model.opponent, &fonts);
-        self.draw_chess_board(&images.board.dark, &images.board.light);
-        self.draw_chess_pieces(&model.us, &model.board, images);
-        self.draw_our_bar(&model.us, &fonts);
+        if let Some(lobby) = &model.lobby {
+            self.draw_lobby(lobby, &fonts);
+        } else if let Some(spectating) = &model.spectating {
+            self.draw_spectating(spectating, images, &fonts);
+        } else if model.simul.is_empty() {
+            self.draw_opponent_bar(
+                &model.opponent,
+                &model.head_to_head,
+                &model.opponent_card,
+                &fonts,
+            );
+            self.draw_chess_board(
+                BOARD_ORIGIN,
+                SQUARE_DIMS,
+                &images.board.dark,
+                &images.board.light,
+            );
+            self.draw_chess_pieces(BOARD_ORIGIN, SQUARE_DIMS, &model.us, &model.board, images);
+            self.draw_our_bar(&model.us, &model.our_rating, &fonts);
+
+            if let Some(rematch) = &model.rematch {
+                self.draw_rematch(rematch, &fonts);
+            }
+        } else {
+            self.draw_simul(&model.simul, images, &fonts);
+        }
 
         self.draw_game_votes(&model.game_votes, &fonts);
-        self.draw_chat_commands(&model.chat_commands, &fonts);
+        self.draw_chat_commands(
+            &model.chat_commands,
+            &model.correspondence,
+            &model.game_chat,
+            &model.opponent_card,
+            &fonts,
+        );
     }
 
     fn as_png_data(&mut self) -> Vec<u8> {
@@ -162,7 +231,7 @@ impl Context {
         self.draw_lines(x + 24.0, y + 24.0, &fonts.retro, 32.0, &notice.lines)
     }
 
-    fn draw_current_state(&mut self, state: &State, fonts: &Fonts) {
+    fn draw_current_state(&mut self, state: &State, challenge_queue: &Vec<String>, fonts: &Fonts) {
         let (x, y) = CURRENT_STATE_ORIGIN;
         let (width, height) = CURRENT_STATE_DIMS;
         let text = state.to_string();
@@ -175,21 +244,31 @@ impl Context {
 
         let color = match state {
             State::ChallengingUser { .. } => brown,
+            State::PickingOpponent => brown,
+            State::Seeking { .. } => brown,
             State::OurTurn => green,
             State::TheirTurn => light_red,
+            State::TeamTurn { .. } => brown,
             State::GameFinished => green,
             State::Unknown => black,
         };
 
         self.draw_coloured_text(x + 24.0, y + 32.0, &fonts.retro, 32.0, &text, color);
+
+        if !challenge_queue.is_empty() {
+            let queue = format!("Up next: {}", challenge_queue.join(", "));
+            self.draw_text(x + 24.0, y + 68.0, &fonts.retro, 24.0, &queue);
+        }
     }
 
     fn draw_settings(&mut self, settings: &Settings, fonts: &Fonts) {
         let (x, y) = SETTINGS_ORIGIN;
         let (width, height) = SETTINGS_DIMS;
         let lines = settings.lines();
+        // Squeeze the lines together if the catalogue has more game modes than fit.
+        let line_height = (200.0 / lines.len() as f32).min(40.0);
         self.draw_box(x, y, width, height);
-        self.draw_lines(x + 24.0, y + 32.0, &fonts.retro, 40.0, &lines)
+        self.draw_lines(x + 24.0, y + 32.0, &fonts.retro, line_height, &lines)
     }
 
     fn draw_move_history(&mut self, move_history: &Vec<String>, fonts: &Fonts) {
@@ -228,20 +307,70 @@ impl Context {
         self.draw_text(x + 12.0, y + 148.0, &fonts.retro, 40.0, &title.url.to_string());
     }
 
-    fn draw_opponent_bar(&mut self, opponent: &Player, fonts: &Fonts) {
+    fn draw_opponent_bar(
+        &mut self,
+        opponent: &Player,
+        head_to_head: &Option<HeadToHead>,
+        card: &Option<OpponentCard>,
+        fonts: &Fonts,
+    ) {
         let (x, y) = OPPONENT_ORIGIN;
-        self.draw_player_bar(x, y, opponent, &fonts.retro);
+        match card.as_ref().and_then(|card| card.title.as_ref()) {
+            Some(title) => {
+                let name = format!("{} {}", title, opponent.name);
+                let opponent = Player { name, ..opponent.clone() };
+                self.draw_player_bar(x, y, &opponent, &fonts.retro);
+            }
+            None => self.draw_player_bar(x, y, opponent, &fonts.retro),
+        }
+
+        if let Some(head_to_head) = head_to_head {
+            self.draw_text(x + 12.0, y + 60.0, &fonts.retro, 28.0, &head_to_head.to_string());
+        }
     }
 
-    fn draw_chess_board(&mut self, dark: &Image, light: &Image) {
+    /// A Lichess TV game in place of ours, with the player at the bottom where we'd be.
+    fn draw_spectating(&mut self, game: &SpectatedGame, images: &Images, fonts: &Fonts) {
+        let (bottom, top) = match game.orientation {
+            chess::Color::White => (&game.white, &game.black),
+            chess::Color::Black => (&game.black, &game.white),
+        };
+
+        let (x, y) = OPPONENT_ORIGIN;
+        self.draw_player_bar(x, y, top, &fonts.retro);
+        self.draw_text(x + 12.0, y + 60.0, &fonts.retro, 28.0, "Spectating Lichess TV");
+
+        self.draw_chess_board(BOARD_ORIGIN, SQUARE_DIMS, &images.board.dark, &images.board.light);
+        self.draw_chess_pieces(BOARD_ORIGIN, SQUARE_DIMS, bottom, &game.board, images);
+
+        let (x, y) = USER_ORIGIN;
+        self.draw_player_bar(x, y, bottom, &fonts.retro);
+    }
+
+    fn draw_chess_board(
+        &mut self,
+        origin: (f32, f32),
+        square_dims: (f32, f32),
+        dark: &Image,
+        light: &Image,
+    ) {
         for x in 0..8 {
             for y in 0..8 {
-                self.draw_chess_square(x, y, dark, light);
+                self.draw_chess_square(origin, square_dims, x, y, dark, light);
             }
         }
     }
 
-    fn draw_chess_pieces(&mut self, us: &Player, board: &chess::Board, images: &Images) {
+    fn draw_chess_pieces(
+        &mut self,
+        origin: (f32, f32),
+        square_dims: (f32, f32),
+        us: &Player,
+        board: &chess::Board,
+        images: &Images,
+    ) {
+        // The padding was tuned for the full size board, so scale it for smaller boards.
+        let scale = square_dims.0 / SQUARE_DIMS.0;
         let (file_offset, rank_offset) =
             if chess::Color::Black == us.color { (7, 0) } else { (0, 7) };
 
@@ -263,58 +392,295 @@ impl Context {
                         chess::Piece::Queen => pieces.queen,
                         chess::Piece::King => pieces.king,
                     };
-                    let x = 6.0 + BOARD_ORIGIN.0 + (SQUARE_DIMS.0 * (file as f32 + 0.0)) as f32;
-                    let mut y = 6.0 + BOARD_ORIGIN.1 + (SQUARE_DIMS.1 * (rank as f32 + 0.0)) as f32;
+                    let x = (6.0 * scale) + origin.0 + (square_dims.0 * (file as f32 + 0.0)) as f32;
+                    let mut y =
+                        (6.0 * scale) + origin.1 + (square_dims.1 * (rank as f32 + 0.0)) as f32;
 
                     if piece == chess::Piece::Pawn {
-                        y -= 4.0;
+                        y -= 4.0 * scale;
                     }
 
-                    self.draw_image(x, y, SQUARE_DIMS.0 - 12.0, SQUARE_DIMS.1 - 12.0, &image);
+                    let (width, height) =
+                        (square_dims.0 - (12.0 * scale), square_dims.1 - (12.0 * scale));
+                    self.draw_image(x, y, width, height, &image);
                 }
             }
         }
     }
 
-    fn draw_chess_square(&mut self, x: i32, y: i32, dark: &Image, light: &Image) {
+    fn draw_chess_square(
+        &mut self,
+        origin: (f32, f32),
+        square_dims: (f32, f32),
+        x: i32,
+        y: i32,
+        dark: &Image,
+        light: &Image,
+    ) {
         let x_even = x % 2 == 0;
         let y_even = y % 2 == 0;
         let is_light = (x_even && y_even) || (!x_even && !y_even);
         let image = if is_light { light } else { dark };
 
         let offset = BORDER_STROKE_WIDTH / 2.0;
-        let x = offset + BOARD_ORIGIN.0 + (x as f32 * SQUARE_DIMS.0);
-        let y = offset + BOARD_ORIGIN.1 + (y as f32 * SQUARE_DIMS.1);
+        let x = offset + origin.0 + (x as f32 * square_dims.0);
+        let y = offset + origin.1 + (y as f32 * square_dims.1);
+
+        self.draw_image(x, y, square_dims.0, square_dims.1, &image);
+    }
+
+    fn draw_lobby(&mut self, lobby: &LobbyView, fonts: &Fonts) {
+        let (x, y) = LOBBY_ORIGIN;
+        let (width, height) = LOBBY_DIMS;
+        self.draw_box(x, y, width, height);
+
+        let header = format!("Next opponent? {}s", lobby.seconds_remaining);
+        self.draw_text(x + 12.0, y + 12.0, &fonts.retro, 48.0, &header);
+        self.draw_text(x + 12.0, y + 64.0, &fonts.retro, 32.0, "Vote with !pick <number>");
+
+        for (index, candidate) in lobby.candidates.iter().enumerate() {
+            let row_y = y + 120.0 + (index as f32 * LOBBY_ROW_HEIGHT);
+            let line = format!("{}. {}", index + 1, candidate.to_string());
+            let votes = format!("   {} votes", candidate.votes);
+            self.draw_text(x + 12.0, row_y, &fonts.retro, 36.0, &line);
+            self.draw_text(x + 12.0, row_y + 48.0, &fonts.retro, 32.0, &votes);
+        }
+    }
+
+    fn draw_rematch(&mut self, rematch: &RematchView, fonts: &Fonts) {
+        let (x, y) = REMATCH_ORIGIN;
+        let (width, height) = REMATCH_DIMS;
+        self.draw_box(x, y, width, height);
+
+        let opponent: String = rematch.opponent.chars().take(15).collect();
+        let header = format!("Rematch {}? {}s", opponent, rematch.seconds_remaining);
+        let votes = format!("!rematch yes: {}  no: {}", rematch.yes, rematch.no);
+        self.draw_text(x + 16.0, y + 16.0, &fonts.retro, 40.0, &header);
+        self.draw_text(x + 16.0, y + 84.0, &fonts.retro, 32.0, &votes);
+        self.draw_text(x + 16.0, y + 144.0, &fonts.retro, 32.0, &rematch.series.to_string());
+    }
+
+    fn draw_simul(&mut self, panels: &Vec<SimulPanel>, images: &Images, fonts: &Fonts) {
+        for (index, panel) in panels.iter().enumerate().take(4) {
+            let column = (index % 2) as f32;
+            let row = (index / 2) as f32;
+
+            let x = SIMUL_ORIGIN.0 + (column * SIMUL_PANEL_DIMS.0);
+            let y = SIMUL_ORIGIN.1 + (row * SIMUL_PANEL_DIMS.1);
+
+            self.draw_simul_panel(x, y, panel, images, fonts);
+        }
+    }
+
+    fn draw_simul_panel(
+        &mut self,
+        x: f32,
+        y: f32,
+        panel: &SimulPanel,
+        images: &Images,
+        fonts: &Fonts,
+    ) {
+        let (width, height) = SIMUL_PANEL_DIMS;
+        self.draw_box(x, y, width, height);
 
-        self.draw_image(x, y, SQUARE_DIMS.0, SQUARE_DIMS.1, &image);
+        let game = &panel.game;
+        let opponent: String = game.opponent.name.chars().take(12).collect();
+        let header = format!("!{} vs {} {}", panel.label, opponent, game.us.timer.to_string());
+        self.draw_text(x + 12.0, y + 10.0, &fonts.retro, 28.0, &header);
+
+        let origin = (x + SIMUL_BOARD_OFFSET.0, y + SIMUL_BOARD_OFFSET.1);
+        let (dark, light) = (&images.board.dark, &images.board.light);
+        self.draw_chess_board(origin, SIMUL_SQUARE_DIMS, dark, light);
+        self.draw_chess_pieces(origin, SIMUL_SQUARE_DIMS, &game.us, &game.position.board, images);
+
+        let mut lines = vec![format!("Votes ({}s left):", panel.votes.seconds_remaining)];
+        let votes = panel.votes.sorted_votes().into_iter().take(2);
+        lines.extend(votes.map(|(vote, stats)| format!("{}: {}", vote, stats.to_string())));
+
+        let votes_y = origin.1 + (8.0 * SIMUL_SQUARE_DIMS.1) + 4.0;
+        self.draw_lines(x + 12.0, votes_y, &fonts.retro, 28.0, &lines);
     }
 
-    fn draw_our_bar(&mut self, us: &Player, fonts: &Fonts) {
+    fn draw_our_bar(&mut self, us: &Player, our_rating: &Option<SessionRating>, fonts: &Fonts) {
         let (x, y) = USER_ORIGIN;
         self.draw_player_bar(x, y, us, &fonts.retro);
+
+        if let Some(our_rating) = our_rating {
+            self.draw_text(x + 12.0, y + 60.0, &fonts.retro, 28.0, &our_rating.to_string());
+
+            let graph_x = x + PLAYER_DIMS.0 - RATING_GRAPH_DIMS.0 - 24.0;
+            self.draw_rating_graph(graph_x, y + 20.0, our_rating);
+        }
+    }
+
+    /// Our rating after each game this stream, scaled between the lowest and highest.
+    fn draw_rating_graph(&mut self, x: f32, y: f32, our_rating: &SessionRating) {
+        let (width, height) = RATING_GRAPH_DIMS;
+        let history = &our_rating.history;
+
+        if history.len() < 2 {
+            return;
+        }
+
+        let min = history.iter().copied().min().unwrap_or_default();
+        let max = history.iter().copied().max().unwrap_or_default();
+        let range = (max - min).max(1) as f32;
+        let step = width / (history.len() - 1) as f32;
+
+        let mut path_builder = PathBuilder::new();
+        for (index, rating) in history.iter().enumerate() {
+            let point_x = x + (index as f32 * step);
+            let point_y = y + height - (((rating - min) as f32 / range) * height);
+
+            if index == 0 {
+                path_builder.move_to(point_x, point_y);
+            } else {
+                path_builder.line_to(point_x, point_y);
+            }
+        }
+
+        let green = SolidSource::from_unpremultiplied_argb(0xff, 73, 133, 53);
+        let light_red = SolidSource::from_unpremultiplied_argb(0xff, 201, 34, 22);
+        let colour = if our_rating.change >= 0 { green } else { light_red };
+
+        let path = path_builder.finish();
+        let options = DrawOptions::new();
+        self.target.stroke(&path, &Source::Solid(colour), &self.strokes.sparkline, &options);
     }
 
     fn draw_game_votes(&mut self, game_votes: &GameVotes, fonts: &Fonts) {
         let (x, y) = GAME_VOTES_ORIGIN;
         let (width, height) = GAME_VOTES_DIMS;
+        let size = 42.0;
         let lines = game_votes.lines();
         self.draw_box(x, y, width, height);
-        self.draw_lines(x + 12.0, y + 12.0, &fonts.retro, 42.0, &lines);
+        self.draw_lines(x + 12.0, y + 12.0, &fonts.retro, size, &lines);
+
+        // The vote lines come last, so the sparklines line up from the bottom of the header.
+        let votes = game_votes.sorted_votes();
+        let first_vote_line = lines.len() - votes.len();
+
+        for (index, (_, vote_stats)) in votes.iter().enumerate() {
+            let line_y = y + 12.0 + ((first_vote_line + index) as f32 * size) + 4.0;
+            if line_y + size > y + height {
+                break;
+            }
+
+            let sparkline_x = x + width - SPARKLINE_DIMS.0 - 24.0;
+            self.draw_sparkline(sparkline_x, line_y + 8.0, vote_stats);
+        }
     }
 
-    fn draw_chat_commands(&mut self, chat_commands: &Vec<Command>, fonts: &Fonts) {
+    fn draw_sparkline(&mut self, x: f32, y: f32, vote_stats: &VoteStats) {
+        let (width, height) = SPARKLINE_DIMS;
+        let history = &vote_stats.history;
+
+        if history.len() < 2 {
+            return;
+        }
+
+        let max = history.iter().copied().max().unwrap_or_default().max(1) as f32;
+        let step = width / (history.len() - 1) as f32;
+
+        let mut path_builder = PathBuilder::new();
+        for (index, total) in history.iter().enumerate() {
+            let point_x = x + (index as f32 * step);
+            let point_y = y + height - ((*total as f32 / max) * height);
+
+            if index == 0 {
+                path_builder.move_to(point_x, point_y);
+            } else {
+                path_builder.line_to(point_x, point_y);
+            }
+        }
+
+        let grey = SolidSource::from_unpremultiplied_argb(0xff, 96, 96, 96);
+        let green = SolidSource::from_unpremultiplied_argb(0xff, 73, 133, 53);
+        let light_red = SolidSource::from_unpremultiplied_argb(0xff, 201, 34, 22);
+
+        let colour = match vote_stats.vote_changes {
+            changes if changes > 0 => green,
+            changes if changes < 0 => light_red,
+            _ => grey,
+        };
+
+        let path = path_builder.finish();
+        let options = DrawOptions::new();
+        self.target.stroke(&path, &Source::Solid(colour), &self.strokes.sparkline, &options);
+    }
+
+    /// Open correspondence games take the bottom few lines, if there are any.
+    fn draw_chat_commands(
+        &mut self,
+        chat_commands: &Vec<Command>,
+        correspondence: &Vec<CorrespondencePanel>,
+        game_chat: &Vec<GameChatLine>,
+        opponent_card: &Option<OpponentCard>,
+        fonts: &Fonts,
+    ) {
         let (x, y) = COMMANDS_ORIGIN;
         let (width, height) = COMMANDS_DIMS;
-        let lines = chat_commands.into_iter().rev().take(14).map(|c| c.to_string()).collect();
+
+        let correspondence: Vec<String> = correspondence
+            .iter()
+            .take(CORRESPONDENCE_MAX_ROWS)
+            .map(|panel| panel.to_string())
+            .collect();
+        // The most recent lines, oldest first.
+        let game_chat: Vec<String> = game_chat
+            .iter()
+            .skip(game_chat.len().saturating_sub(GAME_CHAT_MAX_ROWS))
+            .map(|line| line.to_string())
+            .collect();
+
+        // Each section below the commands takes its rows plus a header.
+        let mut sections = vec![];
+        if let Some(card) = opponent_card {
+            let lines: Vec<String> = card.lines().into_iter().take(OPPONENT_MAX_ROWS).collect();
+            sections.push(("Opponent:", lines));
+        }
+        if !game_chat.is_empty() {
+            sections.push(("Game chat:", game_chat));
+        }
+        if !correspondence.is_empty() {
+            sections.push(("Daily games:", correspondence));
+        }
+        // Later sections are cut short, or left out, rather than run past the bottom of the box.
+        let mut rows_left = COMMANDS_MAX_ROWS;
+        sections.retain_mut(|(_, rows)| {
+            if rows_left < 2 {
+                return false;
+            }
+            rows.truncate(rows_left - 1);
+            rows_left -= rows.len() + 1;
+            true
+        });
+        let command_count = rows_left;
+
+        let lines =
+            chat_commands.into_iter().rev().take(command_count).map(|c| c.to_string()).collect();
         self.draw_box(x, y, width, height);
         self.draw_text(x + 12.0, y + 12.0, &fonts.retro, 42.0, "Chat commands:");
         self.draw_lines(x + 12.0, y + 64.0, &fonts.retro, 32.0, &lines);
+
+        let mut y = y + 64.0 + 32.0 * (command_count as f32);
+        for (header, rows) in sections {
+            let mut lines = vec![header.to_string()];
+            lines.extend(rows);
+            self.draw_lines(x + 12.0, y, &fonts.retro, 32.0, &lines);
+            y += 32.0 * lines.len() as f32;
+        }
     }
 
     fn draw_player_bar(&mut self, x: f32, y: f32, player: &Player, font: &Font) {
         let (width, height) = PLAYER_DIMS;
         self.draw_box(x, y, width, height);
-        self.draw_text(x + 12.0, y + 12.0, font, 40.0, &player.to_string());
+
+        let color = match player.timer.is_low() {
+            true => SolidSource::from_unpremultiplied_argb(0xff, 201, 34, 22),
+            false => self.sources.black.clone(),
+        };
+        self.draw_coloured_text(x + 12.0, y + 12.0, font, 40.0, &player.to_string(), color);
     }
 
     // Utility functions.
@@ -58,6 +58,10 @@ impl Manager {
                 now = std::time::Instant::now();
             }
 
+            if self.model.has_running_clock() {
+                self.frame_manager.set_needs_update();
+            }
+
             if self.frame_manager.needs_update() {
                 let images = self.image_cache.images();
                 let fonts = self.font_cache.fonts();
@@ -104,6 +108,16 @@ impl Manager {
             Notification::State { state } => self.model.state = state,
             Notification::Settings { settings } => self.model.settings = settings,
             Notification::GameVotes { votes } => self.model.game_votes = votes,
+            Notification::Simul { panels } => self.model.simul = panels,
+            Notification::HeadToHead { record } => self.model.head_to_head = record,
+            Notification::OpponentCard { card } => self.model.opponent_card = card,
+            Notification::Lobby { lobby } => self.model.lobby = lobby,
+            Notification::ChallengeQueue { queue } => self.model.challenge_queue = queue,
+            Notification::Correspondence { games } => self.model.correspondence = games,
+            Notification::Rematch { rematch } => self.model.rematch = rematch,
+            Notification::GameChat { lines } => self.model.game_chat = lines,
+            Notification::OurRating { rating } => self.model.set_our_rating(rating),
+            Notification::Spectating { game } => self.model.spectating = game,
             Notification::GameUpdate(game_update) => match game_update {
                 GameUpdate::Board { board } => self.model.board = board,
                 GameUpdate::MoveHistory { moves } => self.model.move_history = moves,
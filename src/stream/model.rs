@@ -1,11 +1,9 @@
 use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use lichess_api::model::Speed;
+use lichess_api::model::{Speed, VariantKey};
 
-use crate::{
-    engine::votes::settings::{GameModes, Settings},
-    lichess::game::Game,
-};
+use crate::{engine::votes::settings::Settings, lichess::game::Game};
 
 pub struct Model {
     pub title: Title,
@@ -13,16 +11,33 @@ pub struct Model {
     pub chat_commands: Vec<Command>,
     pub move_history: Vec<String>,
     pub us: Player,
+    /// Our rating at the speed on stream.
+    pub our_rating: Option<SessionRating>,
     pub opponent: Player,
+    pub head_to_head: Option<HeadToHead>,
+    /// The current opponent's Lichess profile, once fetched.
+    pub opponent_card: Option<OpponentCard>,
     pub board: chess::Board,
     pub settings: Settings,
     pub game_votes: GameVotes,
+    pub simul: Vec<SimulPanel>,
+    pub lobby: Option<LobbyView>,
+    /// Players whose challenges we've accepted, waiting for their game.
+    pub challenge_queue: Vec<String>,
+    pub correspondence: Vec<CorrespondencePanel>,
+    pub rematch: Option<RematchView>,
+    /// The Lichess chat for the game on stream.
+    pub game_chat: Vec<GameChatLine>,
+    /// Shown in place of our game while we wait for one.
+    pub spectating: Option<SpectatedGame>,
     pub state: State,
 }
 
 pub struct Title {
     pub url: &'static str,
     pub speed: Option<Speed>,
+    /// Shown before the speed for games that aren't standard chess.
+    pub variant: Option<&'static str>,
     pub clock_settings: Option<ClockSettings>,
 }
 
@@ -33,7 +48,8 @@ pub struct Notice {
 
 #[derive(Clone)]
 pub struct ClockSettings {
-    pub limit: u32,
+    /// Seconds, as ultrabullet and some custom clocks start with part of a minute.
+    pub initial_seconds: u32,
     pub increment: u32,
 }
 
@@ -51,10 +67,110 @@ pub struct Player {
     pub timer: Timer,
 }
 
+/// The bots chat can pick between for the next game.
+#[derive(Clone)]
+pub struct LobbyView {
+    pub seconds_remaining: u64,
+    pub candidates: Vec<LobbyCandidate>,
+}
+
+/// An open correspondence game, as listed alongside the chat commands.
+#[derive(Clone)]
+pub struct CorrespondencePanel {
+    pub slot: usize,
+    pub opponent: String,
+    pub top_vote: Option<(String, u32)>,
+    pub quota: u32,
+    /// Unix seconds when chat's move is played, or None on the opponent's turn.
+    pub deadline: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct LobbyCandidate {
+    pub name: String,
+    pub rating: u32,
+    pub time_control: String,
+    pub votes: u32,
+}
+
+/// A line from the Lichess game chat, from the opponent or a spectator.
+#[derive(Clone)]
+pub struct GameChatLine {
+    pub username: String,
+    pub text: String,
+}
+
+/// A game from Lichess TV.
+#[derive(Clone)]
+pub struct SpectatedGame {
+    pub white: Player,
+    pub black: Player,
+    /// The side at the bottom of the board.
+    pub orientation: chess::Color,
+    pub board: chess::Board,
+}
+
+/// Chat voting whether to play the last opponent again.
+#[derive(Clone)]
+pub struct RematchView {
+    pub opponent: String,
+    pub seconds_remaining: u64,
+    pub yes: u32,
+    pub no: u32,
+    pub series: Series,
+}
+
+/// Chat's score in games played back to back against the same opponent.
+#[derive(Clone, Copy, Default)]
+pub struct Series {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Our rating, along with how it's moved since the stream started.
+#[derive(Clone)]
+pub struct SessionRating {
+    pub rating: u32,
+    pub change: i32,
+    /// Each rating this stream, oldest first.
+    pub history: Vec<u32>,
+}
+
+/// Chat's record against the current opponent.
+#[derive(Clone, Copy)]
+pub struct HeadToHead {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Longer bios are cut short on the opponent card.
+const BIO_CHARS: usize = 32;
+
+/// What viewers would otherwise ask chat about the current opponent.
+#[derive(Clone)]
+pub struct OpponentCard {
+    pub title: Option<String>,
+    /// Their rating at each perf they've played, most played first, e.g. ("Blitz", 2400).
+    pub ratings: Vec<(&'static str, u32)>,
+    pub bio: Option<String>,
+    pub created_at: Option<SystemTime>,
+    pub games_played: Option<u32>,
+}
+
+/// Tenths of a second are shown below this.
+const TENTHS_BELOW: Duration = Duration::from_secs(10);
+/// The clock is drawn in the low-time colour below this.
+const LOW_TIME: Duration = Duration::from_secs(20);
+
+/// A clock as Lichess last reported it. A running clock counts down from then, so it can
+/// be drawn smoothly between updates.
 #[derive(Copy, Clone)]
 pub struct Timer {
-    pub minutes: u64,
-    pub seconds: u64,
+    remaining: Duration,
+    updated_at: Instant,
+    running: bool,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -64,10 +180,22 @@ pub struct GameVotes {
     pub delays: Delays,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct VoteStats {
     pub vote_changes: i32,
     pub total_votes: u32,
+    /// Votes from Lichess spectators, which aren't in the total.
+    pub spectator_votes: u32,
+    /// Per-second totals, oldest first, ending with the current total.
+    pub history: Vec<u32>,
+}
+
+/// One board of a simul, along with its own votes.
+#[derive(Clone)]
+pub struct SimulPanel {
+    pub label: char,
+    pub game: Game,
+    pub votes: GameVotes,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -78,8 +206,11 @@ pub struct Delays {
 
 pub enum State {
     ChallengingUser { id: String, rating: u32 },
+    PickingOpponent,
+    Seeking { time_control: String },
     OurTurn,
     TheirTurn,
+    TeamTurn { color: chess::Color },
     GameFinished,
     Unknown,
 }
@@ -91,14 +222,41 @@ pub enum Side {
 }
 
 impl Model {
+    /// Whether a clock on screen is counting down, so every frame needs drawing.
+    pub fn has_running_clock(&self) -> bool {
+        let simul_clocks = self.simul.iter().map(|panel| &panel.game.us.timer);
+        let spectated_clocks =
+            self.spectating.iter().flat_map(|game| [&game.white.timer, &game.black.timer]);
+        [&self.us.timer, &self.opponent.timer]
+            .into_iter()
+            .chain(simul_clocks)
+            .chain(spectated_clocks)
+            .any(Timer::is_running)
+    }
+
     pub fn update_from_game(&mut self, game: Game) {
         self.title.speed = game.speed.into();
+        self.title.variant = match game.variant {
+            VariantKey::Chess960 => "Chess960".into(),
+            VariantKey::FromPosition => "Custom".into(),
+            _ => None,
+        };
         self.title.clock_settings = game.clock_settings;
 
-        self.board = game.board;
+        self.board = game.position.board;
         self.move_history = game.move_history.clone();
         self.opponent = game.opponent.clone();
         self.us = game.us.clone();
+        if let Some(our_rating) = &self.our_rating {
+            self.us.rating = self.us.rating.or(our_rating.rating.into());
+        }
+    }
+
+    pub fn set_our_rating(&mut self, our_rating: Option<SessionRating>) {
+        if let Some(our_rating) = &our_rating {
+            self.us.rating = our_rating.rating.into();
+        }
+        self.our_rating = our_rating;
     }
 }
 
@@ -112,27 +270,22 @@ impl Default for Model {
             name: "Twitch".to_string(),
             color: chess::Color::White,
             rating: None,
-            timer: Timer { minutes: 0, seconds: 0 },
+            timer: Timer::new(0),
         };
         let opponent = Player {
             name: "Unknown".to_string(),
             color: chess::Color::Black,
             rating: None,
-            timer: Timer { minutes: 0, seconds: 0 },
+            timer: Timer::new(0),
         };
         let board = chess::Board::default();
-        let settings = Settings {
-            game_modes: GameModes::default(),
-            bullet: 0,
-            rapid: 0,
-            classical: 0,
-            total: 0,
-        };
+        let settings = Settings::default();
         let game_votes = GameVotes {
             seconds_remaining: 30,
             votes: Default::default(),
             delays: Delays { current: 0, max: 6 },
         };
+        let simul = Default::default();
         let state = State::Unknown;
 
         Self {
@@ -141,10 +294,20 @@ impl Default for Model {
             chat_commands,
             move_history,
             us: user,
+            our_rating: None,
             opponent,
+            head_to_head: None,
+            opponent_card: None,
             board,
             settings,
             game_votes,
+            simul,
+            lobby: None,
+            challenge_queue: Default::default(),
+            correspondence: Default::default(),
+            rematch: None,
+            game_chat: Default::default(),
+            spectating: None,
             state,
         }
     }
@@ -152,7 +315,12 @@ impl Default for Model {
 
 impl Title {
     pub fn new() -> Self {
-        Self { url: "lichess.org/@/TTVPlaysChess", speed: None, clock_settings: None }
+        Self {
+            url: "lichess.org/@/TTVPlaysChess",
+            speed: None,
+            variant: None,
+            clock_settings: None,
+        }
     }
 }
 
@@ -176,20 +344,88 @@ impl Command {
 }
 
 impl Timer {
+    /// A stopped clock with this many milliseconds left.
     pub fn new(milliseconds: u64) -> Self {
-        Self { minutes: milliseconds / (60 * 1000), seconds: (milliseconds % (60 * 1000)) / 1000 }
+        Self::reported_at(milliseconds, Instant::now())
+    }
+
+    /// A stopped clock that had this many milliseconds left when Lichess' update arrived, so
+    /// the time the update waited to be handled still counts once it runs.
+    pub fn reported_at(milliseconds: u64, reported_at: Instant) -> Self {
+        Self {
+            remaining: Duration::from_millis(milliseconds),
+            updated_at: reported_at,
+            running: false,
+        }
     }
 
-    pub fn elapse(&mut self, milliseconds: u64) {
-        let total = self.as_millis();
-        let timer =
-            if milliseconds < total { Self::new(total - milliseconds) } else { Self::new(0) };
+    /// Starts or stops the clock. Starting counts down from when it was last reported, and
+    /// stopping keeps the time that's run down so far.
+    pub fn set_running(&mut self, running: bool) {
+        if running == self.running {
+            return;
+        }
 
-        *self = timer;
+        *self = match running {
+            true => Self { running, ..*self },
+            false => Self { remaining: self.remaining(), updated_at: Instant::now(), running },
+        };
     }
 
-    fn as_millis(&self) -> u64 {
-        (self.minutes * 60 * 1000) + (self.seconds * 1000)
+    pub fn is_running(&self) -> bool {
+        self.running && !self.remaining().is_zero()
+    }
+
+    /// An empty clock is left alone, as it's what's shown before any game.
+    pub fn is_low(&self) -> bool {
+        let remaining = self.remaining();
+        !remaining.is_zero() && remaining < LOW_TIME
+    }
+
+    /// The time left now, counting down from the last update if the clock is running.
+    pub fn remaining(&self) -> Duration {
+        match self.running {
+            true => self.remaining.saturating_sub(self.updated_at.elapsed()),
+            false => self.remaining,
+        }
+    }
+}
+
+impl OpponentCard {
+    /// Abbreviated to fit the chat commands panel.
+    pub fn lines(&self) -> Vec<String> {
+        let mut summary = vec![];
+        if let Some(title) = &self.title {
+            summary.push(title.to_string());
+        }
+        if let Some(games_played) = self.games_played {
+            summary.push(format!("{} games", games_played));
+        }
+        let age = self.created_at.and_then(|created_at| created_at.elapsed().ok());
+        if let Some(age) = age {
+            let days = age.as_secs() / 86400;
+            let age = match days {
+                0..=59 => format!("{}d", days),
+                60..=729 => format!("{}mo", days / 30),
+                _ => format!("{}y", days / 365),
+            };
+            summary.push(format!("joined {} ago", age));
+        }
+
+        let mut lines = vec![summary.join(", ")];
+        let ratings: Vec<String> =
+            self.ratings.iter().map(|(perf, rating)| format!("{} {}", perf, rating)).collect();
+        for row in ratings.chunks(3) {
+            lines.push(row.join("  "));
+        }
+        if let Some(bio) = &self.bio {
+            let short: String = bio.chars().take(BIO_CHARS).collect();
+            let ellipsis = if short.len() < bio.len() { "..." } else { "" };
+            lines.push(format!("\"{}{}\"", short, ellipsis));
+        }
+
+        lines.retain(|line| !line.is_empty());
+        lines
     }
 }
 
@@ -202,9 +438,8 @@ impl GameVotes {
             format!("Votes ({} seconds left):", self.seconds_remaining),
         ];
 
-        let mut vote_lines: Vec<(String, VoteStats)> = self.votes.clone().into_iter().collect();
-        vote_lines.sort_by(|l, r| r.1.total_votes.cmp(&l.1.total_votes));
-        let vote_lines: Vec<String> = vote_lines
+        let vote_lines: Vec<String> = self
+            .sorted_votes()
             .into_iter()
             .map(|(chess_move, vote_stats)| format!("{}: {}", chess_move, vote_stats.to_string()))
             .collect();
@@ -215,6 +450,12 @@ impl GameVotes {
 
         lines
     }
+
+    pub fn sorted_votes(&self) -> Vec<(String, VoteStats)> {
+        let mut votes: Vec<(String, VoteStats)> = self.votes.clone().into_iter().collect();
+        votes.sort_by(|l, r| r.1.total_votes.cmp(&l.1.total_votes).then(l.0.cmp(&r.0)));
+        votes
+    }
 }
 
 impl VoteStats {
@@ -254,10 +495,28 @@ impl ToString for Title {
             Speed::Correspondence => "Correspondence",
         };
 
+        let speed = match self.variant {
+            Some(variant) => format!("{} {}", variant, speed),
+            None => speed.to_string(),
+        };
+
         if let Some(clock) = &self.clock_settings {
-            format!("{} ({} + {})", speed, clock.limit, clock.increment)
+            format!("{} ({} + {})", speed, clock.minutes(), clock.increment)
         } else {
-            speed.to_string()
+            speed
+        }
+    }
+}
+
+impl ClockSettings {
+    /// The starting time in minutes, e.g. 0.25 for ultrabullet's 15 seconds.
+    pub fn minutes(&self) -> String {
+        match self.initial_seconds % 60 {
+            0 => (self.initial_seconds / 60).to_string(),
+            _ => {
+                let minutes = format!("{:.2}", self.initial_seconds as f32 / 60.0);
+                minutes.trim_end_matches('0').to_string()
+            }
         }
     }
 }
@@ -276,14 +535,79 @@ impl ToString for Player {
     }
 }
 
+impl ToString for CorrespondencePanel {
+    fn to_string(&self) -> String {
+        let opponent: String = self.opponent.chars().take(15).collect();
+        let Some(deadline) = self.deadline else {
+            return format!("!d{} vs {}: their move", self.slot, opponent);
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let minutes = deadline.saturating_sub(now) / 60;
+        let vote = match &self.top_vote {
+            Some((chess_move, votes)) => format!("{} {}/{}", chess_move, votes, self.quota),
+            None => "no votes".to_string(),
+        };
+
+        let time_left = format!("{}h {}m left", minutes / 60, minutes % 60);
+        format!("!d{} vs {}: {}, {}", self.slot, opponent, vote, time_left)
+    }
+}
+
+impl ToString for GameChatLine {
+    fn to_string(&self) -> String {
+        let username: String = self.username.chars().take(15).collect();
+        let text: String = self.text.chars().take(60).collect();
+        format!("{}: {}", username, text)
+    }
+}
+
+impl ToString for LobbyCandidate {
+    fn to_string(&self) -> String {
+        let name: String = self.name.chars().take(15).collect();
+        format!("{} {} ({})", name, self.rating, self.time_control)
+    }
+}
+
+impl ToString for SessionRating {
+    fn to_string(&self) -> String {
+        match self.change {
+            change if change >= 0 => format!("+{} this stream", change),
+            change => format!("{} this stream", change),
+        }
+    }
+}
+
+impl ToString for Series {
+    fn to_string(&self) -> String {
+        // Counted in half points so draws score a half each.
+        let score = |half_points: u32| match half_points % 2 {
+            0 => (half_points / 2).to_string(),
+            _ => format!("{}.5", half_points / 2),
+        };
+        let ours = score(self.wins * 2 + self.draws);
+        let theirs = score(self.losses * 2 + self.draws);
+
+        format!("Series: Chat {} - {} them", ours, theirs)
+    }
+}
+
+impl ToString for HeadToHead {
+    fn to_string(&self) -> String {
+        format!("Chat vs them: +{} -{} ={}", self.wins, self.losses, self.draws)
+    }
+}
+
 impl ToString for Timer {
     fn to_string(&self) -> String {
-        let extra_minutes = self.seconds / 60;
-        let minutes = self.minutes + extra_minutes;
-        let seconds = self.seconds % 60;
-        let seconds = if seconds <= 9 { format!("0{}", seconds) } else { seconds.to_string() };
+        let remaining = self.remaining();
+        let (minutes, seconds) = (remaining.as_secs() / 60, remaining.as_secs() % 60);
 
-        format!("{}:{}", minutes, seconds)
+        if remaining < TENTHS_BELOW {
+            format!("{}:{:02}.{}", minutes, seconds, remaining.subsec_millis() / 100)
+        } else {
+            format!("{}:{:02}", minutes, seconds)
+        }
     }
 }
 
@@ -299,7 +623,11 @@ impl ToString for VoteStats {
             "".to_string()
         };
 
-        format!("{} {}", self.total_votes, changes)
+        if self.spectator_votes == 0 {
+            return format!("{} {}", self.total_votes, changes);
+        }
+
+        format!("{} {} [{} Lichess]", self.total_votes, changes, self.spectator_votes)
     }
 }
 
@@ -314,6 +642,12 @@ impl ToString for State {
         match self {
             State::OurTurn => "In game: Our turn".to_string(),
             State::TheirTurn => "In game: Their turn".to_string(),
+            State::TeamTurn { color } => {
+                let team = if *color == chess::Color::White { "White" } else { "Black" };
+                format!("Team game: {} to move", team)
+            }
+            State::PickingOpponent => "Picking the next opponent".to_string(),
+            State::Seeking { time_control } => format!("Seeking a {} game", time_control),
             State::GameFinished => "Game finished".to_string(),
             State::Unknown => "Unknown".to_string(),
             State::ChallengingUser { id, rating } => format!("Challenging {} ({})", id, rating),
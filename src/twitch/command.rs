@@ -1,20 +1,57 @@
 use std::str::FromStr;
 
 use lazy_static::lazy_static;
+use lichess_api::model::Speed;
 use regex::Regex;
 
 #[derive(Clone, Debug)]
 pub enum Command {
     VoteGame { action: String },
+    VoteBoard { board: char, action: String },
+    VoteCorrespondence { slot: usize, action: String },
+    JoinTeam { color: chess::Color },
+    PickOpponent { pick: usize },
+    ChallengeMe { username: String },
+    VoteRematch { rematch: bool },
     VoteSetting { setting: Setting, on: bool },
 }
 
+impl Command {
+    fn vote_game_mode(game_mode: GameMode, on: bool) -> Self {
+        Command::VoteSetting { setting: Setting::GameMode(game_mode), on }
+    }
+}
+
 impl ToString for Command {
     fn to_string(&self) -> String {
         match self {
             Command::VoteGame { action } => {
                 format!("{}", &action)
             }
+            Command::VoteBoard { board, action } => {
+                format!("{} {}", board, &action)
+            }
+            Command::VoteCorrespondence { slot, action } => {
+                format!("d{} {}", slot, &action)
+            }
+            Command::JoinTeam { color } => {
+                let color = if *color == chess::Color::White { "white" } else { "black" };
+                format!("join {}", color)
+            }
+            Command::PickOpponent { pick } => {
+                format!("pick {}", pick)
+            }
+            Command::ChallengeMe { username } => {
+                format!("challengeme {}", username)
+            }
+            Command::VoteRematch { rematch } => {
+                let rematch = if *rematch { "yes" } else { "no" };
+                format!("rematch {}", rematch)
+            }
+            Command::VoteSetting { setting: Setting::StartingPosition(key), on } => {
+                let key = if *on { key } else { "off" };
+                format!("position {}", key)
+            }
             Command::VoteSetting { setting, on } => {
                 let on = if *on { "on" } else { "off" };
                 format!("{} {}", setting.to_string(), on)
@@ -26,6 +63,8 @@ impl ToString for Command {
 #[derive(Clone, Debug)]
 pub enum Setting {
     GameMode(GameMode),
+    Chess960,
+    StartingPosition(String),
 }
 
 impl ToString for Setting {
@@ -34,23 +73,56 @@ impl ToString for Setting {
             Setting::GameMode(game_mode) => {
                 format!("{}", game_mode.to_string())
             }
+            Setting::Chess960 => "chess960".to_string(),
+            Setting::StartingPosition(key) => format!("position {}", key),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum GameMode {
+    UltraBullet,
     Bullet,
+    Blitz,
     Rapid,
     Classical,
+    Correspondence,
+}
+
+impl GameMode {
+    pub fn from_speed(speed: &Speed) -> Self {
+        match speed {
+            Speed::UltraBullet => Self::UltraBullet,
+            Speed::Bullet => Self::Bullet,
+            Speed::Blitz => Self::Blitz,
+            Speed::Rapid => Self::Rapid,
+            Speed::Classical => Self::Classical,
+            Speed::Correspondence => Self::Correspondence,
+        }
+    }
+
+    /// The name shown on stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UltraBullet => "Ultrabullet",
+            Self::Bullet => "Bullet",
+            Self::Blitz => "Blitz",
+            Self::Rapid => "Rapid",
+            Self::Classical => "Classical",
+            Self::Correspondence => "Correspondence",
+        }
+    }
 }
 
 impl ToString for GameMode {
     fn to_string(&self) -> String {
         match self {
+            Self::UltraBullet => "ultrabullet",
             Self::Bullet => "bullet",
+            Self::Blitz => "blitz",
             Self::Rapid => "rapid",
             Self::Classical => "classical",
+            Self::Correspondence => "correspondence",
         }
         .to_string()
     }
@@ -61,8 +133,11 @@ impl FromStr for Command {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         lazy_static! {
-            static ref COMMAND_REGEX: Regex =
-                Regex::new(r"!(game|bullet|rapid|classical)\s+(\w+)").unwrap();
+            static ref COMMAND_REGEX: Regex = Regex::new(concat!(
+                r"!(game|ultrabullet|bullet|blitz|rapid|classical|correspondence",
+                r"|chess960|position|join|pick|challengeme|rematch|d[1-9]|[a-d])\s+([\w-]+)"
+            ))
+            .unwrap();
         }
 
         let Some(captures) = COMMAND_REGEX.captures(s) else {
@@ -85,12 +160,40 @@ impl FromStr for Command {
 
         return match command {
             "game" => Ok(Command::VoteGame { action: arg1 }),
-            "bullet" => {
-                Ok(Command::VoteSetting { setting: Setting::GameMode(GameMode::Bullet), on })
+            "join" => match arg1.as_str() {
+                "white" => Ok(Command::JoinTeam { color: chess::Color::White }),
+                "black" => Ok(Command::JoinTeam { color: chess::Color::Black }),
+                _ => Err(crate::error::Error::RegexError),
+            },
+            "challengeme" => Ok(Command::ChallengeMe { username: arg1 }),
+            "rematch" => match arg1.as_str() {
+                "yes" => Ok(Command::VoteRematch { rematch: true }),
+                "no" => Ok(Command::VoteRematch { rematch: false }),
+                _ => Err(crate::error::Error::RegexError),
+            },
+            "pick" => match arg1.parse() {
+                Ok(pick) => Ok(Command::PickOpponent { pick }),
+                Err(_) => Err(crate::error::Error::RegexError),
+            },
+            _ if command.starts_with('d') && command.len() == 2 => {
+                let slot = command[1..].parse().map_err(|_| crate::error::Error::RegexError)?;
+                Ok(Command::VoteCorrespondence { slot, action: arg1 })
+            }
+            "a" | "b" | "c" | "d" => {
+                let board = command.chars().next().unwrap();
+                Ok(Command::VoteBoard { board, action: arg1 })
             }
-            "rapid" => Ok(Command::VoteSetting { setting: Setting::GameMode(GameMode::Rapid), on }),
-            "classical" => {
-                Ok(Command::VoteSetting { setting: Setting::GameMode(GameMode::Classical), on })
+            "ultrabullet" => Ok(Command::vote_game_mode(GameMode::UltraBullet, on)),
+            "bullet" => Ok(Command::vote_game_mode(GameMode::Bullet, on)),
+            "blitz" => Ok(Command::vote_game_mode(GameMode::Blitz, on)),
+            "rapid" => Ok(Command::vote_game_mode(GameMode::Rapid, on)),
+            "classical" => Ok(Command::vote_game_mode(GameMode::Classical, on)),
+            "correspondence" => Ok(Command::vote_game_mode(GameMode::Correspondence, on)),
+            "chess960" => Ok(Command::VoteSetting { setting: Setting::Chess960, on }),
+            "position" => {
+                let on = arg1 != "off";
+                let setting = Setting::StartingPosition(arg1.to_lowercase());
+                Ok(Command::VoteSetting { setting, on })
             }
             _ => Err(crate::error::Error::RegexError),
         };